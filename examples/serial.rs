@@ -26,7 +26,7 @@ fn main() -> ! {
         .freeze(&mut flash.acr);
 
     let mut delay = Delay::new(core.SYST, clocks);
-    let tmr = Timer::tim3(pdev.TIM3, &clocks, &mut rcc.apb1).start_count_down(115_200.hz());
+    let tmr = Timer::tim3(pdev.TIM3, &clocks, &mut rcc.apb1).start_count_down(345_600.hz());
 
     // use 5V tolerant pins to test with UART-to-USB connector
     let tx = gpiob.pb10.into_push_pull_output(&mut gpiob.crh);
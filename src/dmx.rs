@@ -0,0 +1,164 @@
+//! DMX512 transmitter and receiver
+//!
+//! DMX512 packets are sent over a [`Serial`] device at 250000 baud in 8N2
+//! format, usually through an RS-485 transceiver whose driver enable pin is
+//! handled by the device. Each packet starts with a break and a mark after
+//! break, followed by a start code and up to 512 slots of channel data.
+//!
+//! The timer runs at 250 kHz multiplied by the oversampling factor of the
+//! device, so that break and mark after break times, given in bit times, are
+//! multiples of 4 µs.
+
+use crate::serial::{self, DataBits, DriverControl, Parity, Serial, SerialConfig, StopBits};
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal::serial::{Read, Write};
+use embedded_hal::timer::{CountDown, Periodic};
+use nb::block;
+
+/// Number of slots in a universe
+pub const UNIVERSE: usize = 512;
+
+/// Start code of packets holding dimmer levels
+pub const NULL_START_CODE: u8 = 0x00;
+
+/// Shortest break sent, in bit times (92 µs)
+pub const MIN_BREAK_BITS: u16 = 23;
+
+/// Shortest mark after break sent, in bit times (12 µs)
+pub const MIN_MARK_AFTER_BREAK_BITS: u16 = 3;
+
+/// Bit times after which a pause in a packet ends it, 1 ms
+const SLOT_TIMEOUT: u32 = 250;
+
+/// DMX512 error
+#[derive(Debug)]
+pub enum Error<E> {
+    /// Serial communication error
+    Serial(serial::Error<E>),
+    /// Packet had a different start code
+    StartCode(u8),
+}
+
+impl<E> From<serial::Error<E>> for Error<E> {
+    fn from(error: serial::Error<E>) -> Self {
+        Error::Serial(error)
+    }
+}
+
+/// DMX512 transmitter and receiver
+pub struct Dmx<TX, RX, Timer, DE = ()>
+where
+    TX: OutputPin,
+    RX: InputPin,
+    Timer: CountDown + Periodic,
+{
+    serial: Serial<TX, RX, Timer, DE>,
+    break_bits: u16,
+    mark_after_break_bits: u16,
+    start_code: u8,
+    /// Whether the break of the next packet was already received
+    break_received: bool,
+}
+
+impl<TX, RX, Timer, DE, E> Dmx<TX, RX, Timer, DE>
+where
+    TX: OutputPin<Error = E>,
+    RX: InputPin<Error = E>,
+    Timer: CountDown + Periodic,
+    DE: DriverControl<E>,
+{
+    /// Create instance, setting `serial` to 8N2
+    ///
+    /// Packets are sent with a break of 44 bit times (176 µs) and a mark
+    /// after break of 4 bit times (16 µs), and received with the null start
+    /// code.
+    pub fn new(mut serial: Serial<TX, RX, Timer, DE>) -> Self {
+        serial.set_config(SerialConfig {
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::Two,
+        });
+        Dmx {
+            serial,
+            break_bits: 44,
+            mark_after_break_bits: 4,
+            start_code: NULL_START_CODE,
+            break_received: false,
+        }
+    }
+
+    /// Set the lengths of the break and of the mark after break of sent
+    /// packets, in bit times
+    ///
+    /// Lengths below [`MIN_BREAK_BITS`] and [`MIN_MARK_AFTER_BREAK_BITS`] are
+    /// raised to these minimums.
+    pub fn set_timing(&mut self, break_bits: u16, mark_after_break_bits: u16) {
+        self.break_bits = break_bits.max(MIN_BREAK_BITS);
+        self.mark_after_break_bits = mark_after_break_bits.max(MIN_MARK_AFTER_BREAK_BITS);
+    }
+
+    /// Set the start code of received packets
+    pub fn set_start_code(&mut self, start_code: u8) {
+        self.start_code = start_code;
+    }
+
+    /// Send a packet with `start_code` and at most [`UNIVERSE`] `slots`
+    ///
+    /// The line driver, if any, is released once the last slot is sent.
+    pub fn send(&mut self, start_code: u8, slots: &[u8]) -> Result<(), Error<E>> {
+        // send_break already ends with one bit time of mark
+        self.serial.send_break(self.break_bits)?;
+        self.serial
+            .wait_for_bits(self.mark_after_break_bits.saturating_sub(1));
+
+        let slots = &slots[..slots.len().min(UNIVERSE)];
+        for slot in core::iter::once(&start_code).chain(slots) {
+            block!(self.serial.write(*slot))?;
+        }
+        block!(Write::<u8>::flush(&mut self.serial))?;
+        Ok(())
+    }
+
+    /// Receive a packet into `slots`, returning the number of slots received
+    ///
+    /// Returns `WouldBlock` until a break is seen. The packet ends when
+    /// `slots` is full, on the break of the next packet, or after a pause of
+    /// 1 ms. Returns `Error::StartCode` for packets with another start code
+    /// than set with [`set_start_code`](Self::set_start_code).
+    ///
+    /// Like [`Serial`]'s `read`, this must be polled at least once per timer
+    /// tick while waiting for data.
+    pub fn receive(&mut self, slots: &mut [u8]) -> nb::Result<usize, Error<E>> {
+        if !self.break_received {
+            match Read::<u8>::read(&mut self.serial) {
+                Err(nb::Error::Other(serial::Error::Break)) => {}
+                Err(nb::Error::Other(error)) => return Err(nb::Error::Other(error.into())),
+                Err(nb::Error::WouldBlock) | Ok(_) => return Err(nb::Error::WouldBlock),
+            }
+        }
+        self.break_received = false;
+
+        let start_code: u8 = self
+            .serial
+            .read_timeout(SLOT_TIMEOUT)
+            .map_err(Error::from)?;
+        if start_code != self.start_code {
+            return Err(nb::Error::Other(Error::StartCode(start_code)));
+        }
+
+        let mut count = 0;
+        for slot in slots.iter_mut() {
+            match self.serial.read_timeout(SLOT_TIMEOUT) {
+                Ok(value) => *slot = value,
+                Err(serial::Error::Timeout) => break,
+                Err(serial::Error::Break) => {
+                    self.break_received = true;
+                    break;
+                }
+                Err(error) => return Err(nb::Error::Other(error.into())),
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+}
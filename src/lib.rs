@@ -10,6 +10,11 @@
 #![no_std]
 #![deny(missing_docs)]
 
+pub mod dmx;
 pub mod i2c;
+pub mod lin;
+pub mod midi;
+pub mod modbus;
+pub mod sbus;
 pub mod serial;
 pub mod spi;
@@ -0,0 +1,342 @@
+//! LIN bus master and slave
+//!
+//! LIN frames are sent over a [`Serial`] device in 8N1 format, usually at
+//! 19200 baud or below, through a LIN transceiver. The receiving side of the
+//! device should use oversampling, as the master and slave clocks may differ.
+//!
+//! A frame starts with a header sent by the master: a break, the sync
+//! character `0x55`, and the protected identifier of the frame. The
+//! response, up to 8 data bytes and a checksum, is then published either by
+//! the master or by a slave.
+//!
+//! The [`Master`] runs a schedule table of [`Slot`]s:
+//!
+//! ```ignore
+//! const SCHEDULE: [Slot; 2] = [
+//!     Slot { id: 0x10, direction: Direction::Publish, checksum: Checksum::Enhanced, delay: 20 },
+//!     Slot { id: 0x11, direction: Direction::Subscribe, checksum: Checksum::Enhanced, delay: 20 },
+//! ];
+//!
+//! let mut master = Master::new(serial);
+//! let mut command = [0; 2];
+//! let mut status = [0; 4];
+//! for slot in SCHEDULE.iter().cycle() {
+//!     let data = if slot.id == 0x10 { &mut command[..] } else { &mut status[..] };
+//!     master.run_slot(slot, data).ok();
+//! }
+//! ```
+//!
+//! A [`Slave`] detects headers and calls back a [`Handler`] to publish or
+//! receive the response.
+
+use crate::serial::{self, Serial};
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal::serial::Write;
+use embedded_hal::timer::{CountDown, Periodic};
+use nb::block;
+
+/// Length of the break sent by the master, in bit times
+const BREAK_BITS: u16 = 13;
+
+/// Sync character following the break
+const SYNC: u8 = 0x55;
+
+/// Bit times to wait for each byte of a header or response
+const BYTE_TIMEOUT: u32 = 20;
+
+/// Maximum number of data bytes in a frame
+pub const MAX_DATA: usize = 8;
+
+/// LIN error
+#[derive(Debug)]
+pub enum Error<E> {
+    /// Serial communication error
+    Serial(serial::Error<E>),
+    /// Identifier is over 63
+    InvalidId,
+    /// Response is longer than [`MAX_DATA`] bytes
+    DataLength,
+    /// Sync field was not `0x55`
+    Sync,
+    /// Parity bits of the protected identifier were wrong
+    IdParity,
+    /// Checksum didn't match the response
+    Checksum,
+    /// No response, or an incomplete one, was received
+    NoResponse,
+}
+
+impl<E> From<serial::Error<E>> for Error<E> {
+    fn from(error: serial::Error<E>) -> Self {
+        Error::Serial(error)
+    }
+}
+
+/// Checksum model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    /// LIN 1.x, over the data bytes only
+    Classic,
+    /// LIN 2.x, over the protected identifier and the data bytes, except for
+    /// diagnostic frames which always use the classic checksum
+    Enhanced,
+}
+
+/// Protected identifier of frame `id`, with its two parity bits
+///
+/// Returns `None` if `id` is over 63.
+pub fn protected_id(id: u8) -> Option<u8> {
+    if id > 0x3F {
+        return None;
+    }
+    let bit = |n: u8| (id >> n) & 1;
+    let p0 = bit(0) ^ bit(1) ^ bit(2) ^ bit(4);
+    let p1 = !(bit(1) ^ bit(3) ^ bit(4) ^ bit(5)) & 1;
+    Some(id | p0 << 6 | p1 << 7)
+}
+
+/// Checksum of a response with protected identifier `pid`
+pub fn checksum(model: Checksum, pid: u8, data: &[u8]) -> u8 {
+    let id = pid & 0x3F;
+    let diagnostic = id == 0x3C || id == 0x3D;
+    let initial = match model {
+        Checksum::Enhanced if !diagnostic => u16::from(pid),
+        _ => 0,
+    };
+    let sum = data.iter().fold(initial, |sum, byte| {
+        let sum = sum + u16::from(*byte);
+        if sum > 0xFF {
+            sum - 0xFF
+        } else {
+            sum
+        }
+    });
+    !(sum as u8)
+}
+
+/// Node publishing the response of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// The master sends the response
+    Publish,
+    /// A slave sends the response, received by the master
+    Subscribe,
+}
+
+/// Entry of a master schedule table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slot {
+    /// Frame identifier, from 0 to 63
+    pub id: u8,
+    /// Node publishing the response
+    pub direction: Direction,
+    /// Checksum model of the frame
+    pub checksum: Checksum,
+    /// Bit times to wait after the frame, before the next slot
+    pub delay: u16,
+}
+
+/// LIN master node
+pub struct Master<TX, RX, Timer>
+where
+    TX: OutputPin,
+    RX: InputPin,
+    Timer: CountDown + Periodic,
+{
+    serial: Serial<TX, RX, Timer>,
+}
+
+impl<TX, RX, Timer, E> Master<TX, RX, Timer>
+where
+    TX: OutputPin<Error = E>,
+    RX: InputPin<Error = E>,
+    Timer: CountDown + Periodic,
+{
+    /// Create instance
+    pub fn new(serial: Serial<TX, RX, Timer>) -> Self {
+        Master { serial }
+    }
+
+    /// Send the header of frame `id`: break, sync and protected identifier
+    pub fn send_header(&mut self, id: u8) -> Result<(), Error<E>> {
+        let pid = protected_id(id).ok_or(Error::InvalidId)?;
+        self.serial.send_break(BREAK_BITS)?;
+        block!(self.serial.write(SYNC))?;
+        block!(self.serial.write(pid))?;
+        Ok(())
+    }
+
+    /// Send frame `id` with `data` as response
+    ///
+    /// Returns `Error::DataLength` without sending anything if `data` is
+    /// longer than [`MAX_DATA`].
+    pub fn write_frame(&mut self, id: u8, data: &[u8], model: Checksum) -> Result<(), Error<E>> {
+        check_length(data)?;
+        self.send_header(id)?;
+        let pid = protected_id(id).ok_or(Error::InvalidId)?;
+        for byte in data.iter().chain(&[checksum(model, pid, data)]) {
+            block!(self.serial.write(*byte))?;
+        }
+        Ok(())
+    }
+
+    /// Send the header of frame `id`, then read its response from a slave
+    /// into `data`, whose length is the expected number of data bytes
+    ///
+    /// Returns `Error::DataLength` without sending anything if `data` is
+    /// longer than [`MAX_DATA`].
+    pub fn read_frame(&mut self, id: u8, data: &mut [u8], model: Checksum) -> Result<(), Error<E>> {
+        check_length(data)?;
+        self.send_header(id)?;
+        let pid = protected_id(id).ok_or(Error::InvalidId)?;
+        read_response(&mut self.serial, pid, data, model)
+    }
+
+    /// Run one schedule table entry, publishing `data` or receiving into it
+    pub fn run_slot(&mut self, slot: &Slot, data: &mut [u8]) -> Result<(), Error<E>> {
+        let result = match slot.direction {
+            Direction::Publish => self.write_frame(slot.id, data, slot.checksum),
+            Direction::Subscribe => self.read_frame(slot.id, data, slot.checksum),
+        };
+        self.serial.wait_for_bits(slot.delay);
+        result
+    }
+}
+
+/// Action of a slave on a header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Response {
+    /// The frame is not handled by this slave
+    Ignore,
+    /// Publish this many data bytes
+    Publish(usize),
+    /// Receive this many data bytes
+    Subscribe(usize),
+}
+
+/// Frame handling of a [`Slave`]
+pub trait Handler {
+    /// Called on a header for frame `id`
+    ///
+    /// To publish the response, write it into `data` and return its length.
+    fn on_header(&mut self, id: u8, data: &mut [u8; MAX_DATA]) -> Response;
+
+    /// Called with a response received for a subscribed frame `id`
+    fn on_response(&mut self, id: u8, data: &[u8]);
+}
+
+/// LIN slave node
+pub struct Slave<TX, RX, Timer>
+where
+    TX: OutputPin,
+    RX: InputPin,
+    Timer: CountDown + Periodic,
+{
+    serial: Serial<TX, RX, Timer>,
+    checksum: Checksum,
+}
+
+impl<TX, RX, Timer, E> Slave<TX, RX, Timer>
+where
+    TX: OutputPin<Error = E>,
+    RX: InputPin<Error = E>,
+    Timer: CountDown + Periodic,
+{
+    /// Create instance, using enhanced checksums
+    pub fn new(serial: Serial<TX, RX, Timer>) -> Self {
+        Slave {
+            serial,
+            checksum: Checksum::Enhanced,
+        }
+    }
+
+    /// Set the checksum model of all frames
+    pub fn set_checksum(&mut self, model: Checksum) {
+        self.checksum = model;
+    }
+
+    /// Wait for a header, then publish or receive its response as decided by
+    /// `handler`
+    ///
+    /// Returns `WouldBlock` while no break is seen. Other bytes on the bus
+    /// are skipped.
+    ///
+    /// Like [`Serial`]'s `read`, this must be polled at least once per timer
+    /// tick while waiting for data.
+    pub fn poll<H: Handler>(&mut self, handler: &mut H) -> nb::Result<(), Error<E>> {
+        match embedded_hal::serial::Read::<u8>::read(&mut self.serial) {
+            Err(nb::Error::Other(serial::Error::Break)) => {}
+            Err(nb::Error::WouldBlock) | Ok(_) => return Err(nb::Error::WouldBlock),
+            Err(nb::Error::Other(error)) => return Err(nb::Error::Other(error.into())),
+        }
+
+        let sync: u8 = self
+            .serial
+            .read_timeout(BYTE_TIMEOUT)
+            .map_err(Error::from)?;
+        if sync != SYNC {
+            return Err(nb::Error::Other(Error::Sync));
+        }
+        let pid: u8 = self
+            .serial
+            .read_timeout(BYTE_TIMEOUT)
+            .map_err(Error::from)?;
+        let id = pid & 0x3F;
+        if protected_id(id) != Some(pid) {
+            return Err(nb::Error::Other(Error::IdParity));
+        }
+
+        let mut data = [0; MAX_DATA];
+        match handler.on_header(id, &mut data) {
+            Response::Ignore => {}
+            Response::Publish(len) => {
+                // the pid is read in the middle of its stop bit, leave the end
+                // of it to the master
+                self.serial.wait_for_bits(1);
+                let data = &data[..len.min(MAX_DATA)];
+                for byte in data.iter().chain(&[checksum(self.checksum, pid, data)]) {
+                    block!(self.serial.write(*byte)).map_err(Error::from)?;
+                }
+            }
+            Response::Subscribe(len) => {
+                let data = &mut data[..len.min(MAX_DATA)];
+                read_response(&mut self.serial, pid, data, self.checksum)?;
+                handler.on_response(id, data);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check that a response of `data` fits in a frame
+fn check_length<E>(data: &[u8]) -> Result<(), Error<E>> {
+    if data.len() > MAX_DATA {
+        return Err(Error::DataLength);
+    }
+    Ok(())
+}
+
+/// Read a response of `data.len()` bytes and its checksum
+fn read_response<TX, RX, Timer, E>(
+    serial: &mut Serial<TX, RX, Timer>,
+    pid: u8,
+    data: &mut [u8],
+    model: Checksum,
+) -> Result<(), Error<E>>
+where
+    TX: OutputPin<Error = E>,
+    RX: InputPin<Error = E>,
+    Timer: CountDown + Periodic,
+{
+    let mut read = || match serial.read_timeout(BYTE_TIMEOUT) {
+        Err(serial::Error::Timeout) => Err(Error::NoResponse),
+        result => result.map_err(Error::from),
+    };
+    for byte in data.iter_mut() {
+        *byte = read()?;
+    }
+    if read()? != checksum(model, pid, data) {
+        return Err(Error::Checksum);
+    }
+    Ok(())
+}
@@ -0,0 +1,403 @@
+//! MIDI port and message parser
+//!
+//! MIDI is sent over a [`Serial`] device at 31250 baud in 8N1 format, so the
+//! timer runs at 31250 Hz multiplied by the oversampling factor of the
+//! device.
+//!
+//! Received bytes are turned into [`Message`]s by a streaming [`Parser`],
+//! which also works on its own, e.g. on bytes queued by an interrupt-driven
+//! receiver. It handles running status, System Real-Time messages
+//! interleaved in other messages, and System Exclusive messages.
+
+use crate::serial::{self, Serial};
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal::serial::{Read, Write};
+use embedded_hal::timer::{CountDown, Periodic};
+use nb::block;
+
+/// Baud rate of MIDI
+pub const BAUD_RATE: u32 = 31250;
+
+const SYSEX_START: u8 = 0xF0;
+const SYSEX_END: u8 = 0xF7;
+
+/// MIDI message
+///
+/// Channels are numbered from 0 to 15, and data values are 7 bits. A
+/// `NoteOn` with a velocity of 0 is reported as such, though it usually means
+/// `NoteOff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message<'a> {
+    /// Note released
+    NoteOff {
+        /// Channel
+        channel: u8,
+        /// Note number
+        note: u8,
+        /// Release velocity
+        velocity: u8,
+    },
+    /// Note pressed
+    NoteOn {
+        /// Channel
+        channel: u8,
+        /// Note number
+        note: u8,
+        /// Velocity
+        velocity: u8,
+    },
+    /// Polyphonic key pressure
+    PolyPressure {
+        /// Channel
+        channel: u8,
+        /// Note number
+        note: u8,
+        /// Pressure
+        pressure: u8,
+    },
+    /// Control change
+    ControlChange {
+        /// Channel
+        channel: u8,
+        /// Controller number
+        controller: u8,
+        /// Controller value
+        value: u8,
+    },
+    /// Program change
+    ProgramChange {
+        /// Channel
+        channel: u8,
+        /// Program number
+        program: u8,
+    },
+    /// Channel pressure
+    ChannelPressure {
+        /// Channel
+        channel: u8,
+        /// Pressure
+        pressure: u8,
+    },
+    /// Pitch bend
+    PitchBend {
+        /// Channel
+        channel: u8,
+        /// 14-bit value, 8192 being centered
+        value: u16,
+    },
+    /// System Exclusive data, without the start and end bytes
+    SysEx(&'a [u8]),
+    /// MIDI time code quarter frame
+    TimeCode(u8),
+    /// Song position pointer, in MIDI beats
+    SongPosition(u16),
+    /// Song select
+    SongSelect(u8),
+    /// Tune request
+    TuneRequest,
+    /// Timing clock
+    Clock,
+    /// Start
+    Start,
+    /// Continue
+    Continue,
+    /// Stop
+    Stop,
+    /// Active sensing
+    ActiveSensing,
+    /// System reset
+    Reset,
+}
+
+impl Message<'_> {
+    /// Bytes of a message other than `SysEx`, and their number
+    fn short_bytes(&self) -> ([u8; 3], usize) {
+        let channel = |status: u8, channel: u8| status | (channel & 0x0F);
+        match *self {
+            Message::NoteOff {
+                channel: ch,
+                note,
+                velocity,
+            } => ([channel(0x80, ch), note, velocity], 3),
+            Message::NoteOn {
+                channel: ch,
+                note,
+                velocity,
+            } => ([channel(0x90, ch), note, velocity], 3),
+            Message::PolyPressure {
+                channel: ch,
+                note,
+                pressure,
+            } => ([channel(0xA0, ch), note, pressure], 3),
+            Message::ControlChange {
+                channel: ch,
+                controller,
+                value,
+            } => ([channel(0xB0, ch), controller, value], 3),
+            Message::ProgramChange {
+                channel: ch,
+                program,
+            } => ([channel(0xC0, ch), program, 0], 2),
+            Message::ChannelPressure {
+                channel: ch,
+                pressure,
+            } => ([channel(0xD0, ch), pressure, 0], 2),
+            Message::PitchBend { channel: ch, value } => {
+                ([channel(0xE0, ch), value as u8, (value >> 7) as u8], 3)
+            }
+            Message::TimeCode(value) => ([0xF1, value, 0], 2),
+            Message::SongPosition(beats) => ([0xF2, beats as u8, (beats >> 7) as u8], 3),
+            Message::SongSelect(song) => ([0xF3, song, 0], 2),
+            Message::TuneRequest => ([0xF6, 0, 0], 1),
+            Message::Clock => ([0xF8, 0, 0], 1),
+            Message::Start => ([0xFA, 0, 0], 1),
+            Message::Continue => ([0xFB, 0, 0], 1),
+            Message::Stop => ([0xFC, 0, 0], 1),
+            Message::ActiveSensing => ([0xFE, 0, 0], 1),
+            Message::Reset => ([0xFF, 0, 0], 1),
+            Message::SysEx(_) => {
+                unreachable!("SysEx messages are sent byte by byte by Midi::send")
+            }
+        }
+    }
+}
+
+/// Number of data bytes following `status`, for messages other than
+/// System Real-Time and System Exclusive
+fn data_len(status: u8) -> Option<usize> {
+    match status {
+        0x80..=0xBF | 0xE0..=0xEF | 0xF2 => Some(2),
+        0xC0..=0xDF | 0xF1 | 0xF3 => Some(1),
+        0xF6 => Some(0),
+        _ => None,
+    }
+}
+
+/// Kind of message completed by a received byte
+#[derive(Clone, Copy)]
+enum Complete {
+    RealTime(Message<'static>),
+    SysEx,
+    Status(u8),
+}
+
+/// Streaming MIDI parser, keeping up to `N` bytes of System Exclusive data
+///
+/// Further System Exclusive bytes are dropped.
+pub struct Parser<const N: usize> {
+    /// Status of the message being received, kept for running status
+    status: Option<u8>,
+    data: [u8; 2],
+    data_len: usize,
+    sysex: [u8; N],
+    sysex_len: usize,
+    in_sysex: bool,
+}
+
+impl<const N: usize> Parser<N> {
+    /// Create instance
+    pub const fn new() -> Self {
+        Parser {
+            status: None,
+            data: [0; 2],
+            data_len: 0,
+            sysex: [0; N],
+            sysex_len: 0,
+            in_sysex: false,
+        }
+    }
+
+    /// Parse a received byte, returning the message it completes, if any
+    pub fn feed(&mut self, byte: u8) -> Option<Message<'_>> {
+        let complete = self.push(byte)?;
+        Some(self.message(complete))
+    }
+
+    /// Parse `byte`, returning what it completes without borrowing the parser
+    fn push(&mut self, byte: u8) -> Option<Complete> {
+        match byte {
+            0xF8..=0xFF => return realtime(byte).map(Complete::RealTime),
+            SYSEX_START => {
+                self.status = None;
+                self.in_sysex = true;
+                self.sysex_len = 0;
+                return None;
+            }
+            SYSEX_END => {
+                self.status = None;
+                if core::mem::replace(&mut self.in_sysex, false) {
+                    return Some(Complete::SysEx);
+                }
+                return None;
+            }
+            0x80..=0xFF => {
+                // any other status ends System Exclusive data
+                self.in_sysex = false;
+                self.status = Some(byte);
+                self.data_len = 0;
+            }
+            _ if self.in_sysex => {
+                if self.sysex_len < N {
+                    self.sysex[self.sysex_len] = byte;
+                    self.sysex_len += 1;
+                }
+                return None;
+            }
+            _ => {
+                if self.status.is_none() || self.data_len == self.data.len() {
+                    return None;
+                }
+                self.data[self.data_len] = byte;
+                self.data_len += 1;
+            }
+        }
+
+        let status = self.status?;
+        let len = match data_len(status) {
+            Some(len) => len,
+            None => {
+                self.status = None;
+                return None;
+            }
+        };
+        if self.data_len < len {
+            return None;
+        }
+
+        // running status only applies to channel messages
+        self.data_len = 0;
+        if status >= 0xF0 {
+            self.status = None;
+        }
+        Some(Complete::Status(status))
+    }
+
+    /// Message completed by the last received byte
+    fn message(&self, complete: Complete) -> Message<'_> {
+        let status = match complete {
+            Complete::RealTime(message) => return message,
+            Complete::SysEx => return Message::SysEx(&self.sysex[..self.sysex_len]),
+            Complete::Status(status) => status,
+        };
+        let channel = status & 0x0F;
+        let [first, second] = self.data;
+        let value14 = u16::from(first) | u16::from(second) << 7;
+        match status {
+            0x80..=0x8F => Message::NoteOff {
+                channel,
+                note: first,
+                velocity: second,
+            },
+            0x90..=0x9F => Message::NoteOn {
+                channel,
+                note: first,
+                velocity: second,
+            },
+            0xA0..=0xAF => Message::PolyPressure {
+                channel,
+                note: first,
+                pressure: second,
+            },
+            0xB0..=0xBF => Message::ControlChange {
+                channel,
+                controller: first,
+                value: second,
+            },
+            0xC0..=0xCF => Message::ProgramChange {
+                channel,
+                program: first,
+            },
+            0xD0..=0xDF => Message::ChannelPressure {
+                channel,
+                pressure: first,
+            },
+            0xE0..=0xEF => Message::PitchBend {
+                channel,
+                value: value14,
+            },
+            0xF1 => Message::TimeCode(first),
+            0xF2 => Message::SongPosition(value14),
+            0xF3 => Message::SongSelect(first),
+            _ => Message::TuneRequest,
+        }
+    }
+}
+
+impl<const N: usize> Default for Parser<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// System Real-Time message of status `byte`
+fn realtime(byte: u8) -> Option<Message<'static>> {
+    match byte {
+        0xF8 => Some(Message::Clock),
+        0xFA => Some(Message::Start),
+        0xFB => Some(Message::Continue),
+        0xFC => Some(Message::Stop),
+        0xFE => Some(Message::ActiveSensing),
+        0xFF => Some(Message::Reset),
+        _ => None,
+    }
+}
+
+/// MIDI port, keeping up to `N` bytes of received System Exclusive data
+pub struct Midi<TX, RX, Timer, const N: usize>
+where
+    TX: OutputPin,
+    RX: InputPin,
+    Timer: CountDown + Periodic,
+{
+    serial: Serial<TX, RX, Timer>,
+    parser: Parser<N>,
+}
+
+impl<TX, RX, Timer, E, const N: usize> Midi<TX, RX, Timer, N>
+where
+    TX: OutputPin<Error = E>,
+    RX: InputPin<Error = E>,
+    Timer: CountDown + Periodic,
+{
+    /// Create instance
+    pub fn new(serial: Serial<TX, RX, Timer>) -> Self {
+        Midi {
+            serial,
+            parser: Parser::new(),
+        }
+    }
+
+    /// Send `message`, with data values truncated to 7 bits
+    pub fn send(&mut self, message: &Message) -> Result<(), serial::Error<E>> {
+        if let Message::SysEx(data) = message {
+            block!(self.serial.write(SYSEX_START))?;
+            for byte in data.iter() {
+                block!(self.serial.write(byte & 0x7F))?;
+            }
+            return block!(self.serial.write(SYSEX_END));
+        }
+
+        let (bytes, len) = message.short_bytes();
+        block!(self.serial.write(bytes[0]))?;
+        for byte in &bytes[1..len] {
+            block!(self.serial.write(byte & 0x7F))?;
+        }
+        Ok(())
+    }
+
+    /// Receive bytes until a message is complete
+    ///
+    /// Returns `WouldBlock` while no byte is being received; a partially
+    /// received message is kept for the next call.
+    ///
+    /// Like [`Serial`]'s `read`, this must be polled at least once per timer
+    /// tick while waiting for data.
+    pub fn receive(&mut self) -> nb::Result<Message<'_>, serial::Error<E>> {
+        loop {
+            let byte = Read::<u8>::read(&mut self.serial)?;
+            if let Some(complete) = self.parser.push(byte) {
+                return Ok(self.parser.message(complete));
+            }
+        }
+    }
+}
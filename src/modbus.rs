@@ -0,0 +1,443 @@
+//! Modbus RTU master
+//!
+//! Modbus RTU frames are sent over a [`Serial`] device, usually through an
+//! RS-485 transceiver whose driver enable pin is handled by the device. The
+//! master sets the device to 8E1 format, the Modbus default; see
+//! [`Master::set_parity`] for slaves using another parity.
+//!
+//! A frame holds the slave address, a function code, its data and a CRC-16.
+//! Frames are separated by at least 3.5 character times of silence, which is
+//! how the end of a response is detected. This gap is measured with the
+//! timer of the device; the Modbus specification fixes it to 1.75 ms above
+//! 19200 baud, which [`Master::set_frame_gap`] can be used for.
+//!
+//! ```ignore
+//! let mut master = Master::new(serial);
+//! let mut registers = [0; 2];
+//! master.read_holding_registers(1, 0x0100, &mut registers)?;
+//! master.write_single_coil(1, 0x0003, true)?;
+//! ```
+
+use crate::serial::{self, DataBits, DriverControl, Parity, Serial, SerialConfig, StopBits};
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal::serial::Write;
+use embedded_hal::timer::{CountDown, Periodic};
+use nb::block;
+
+/// Default silence between frames, in bit times: 3.5 characters of 11 bits
+const FRAME_GAP: u16 = 39;
+
+/// Default time to wait for the start of a response, in bit times
+const RESPONSE_TIMEOUT: u32 = 2000;
+
+/// Maximum length of a frame, including the address and CRC
+pub const MAX_FRAME: usize = 256;
+
+/// Address of broadcast requests, which slaves don't respond to
+pub const BROADCAST: u8 = 0;
+
+const READ_COILS: u8 = 0x01;
+const READ_DISCRETE_INPUTS: u8 = 0x02;
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+const READ_INPUT_REGISTERS: u8 = 0x04;
+const WRITE_SINGLE_COIL: u8 = 0x05;
+const WRITE_SINGLE_REGISTER: u8 = 0x06;
+const WRITE_MULTIPLE_COILS: u8 = 0x0F;
+const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+/// Modbus error
+#[derive(Debug)]
+pub enum Error<E> {
+    /// Serial communication error
+    Serial(serial::Error<E>),
+    /// Number of coils or registers is out of range for the function, or
+    /// a read was broadcast
+    InvalidRequest,
+    /// No response was received
+    NoResponse,
+    /// CRC didn't match the response
+    Crc,
+    /// Response doesn't match the request
+    InvalidResponse,
+    /// Slave responded with an exception
+    Exception(Exception),
+}
+
+impl<E> From<serial::Error<E>> for Error<E> {
+    fn from(error: serial::Error<E>) -> Self {
+        Error::Serial(error)
+    }
+}
+
+/// Exception code of an exception response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exception {
+    /// Function code is not supported by the slave
+    IllegalFunction,
+    /// Address range is not available on the slave
+    IllegalDataAddress,
+    /// Value in the request is not allowed
+    IllegalDataValue,
+    /// Slave failed to perform the request
+    ServerDeviceFailure,
+    /// Request accepted, but it will take long to complete
+    Acknowledge,
+    /// Slave is busy with a long request
+    ServerDeviceBusy,
+    /// Slave detected a parity error in its memory
+    MemoryParityError,
+    /// Gateway couldn't route the request
+    GatewayPathUnavailable,
+    /// Target device behind a gateway didn't respond
+    GatewayTargetFailed,
+    /// Other exception code
+    Other(u8),
+}
+
+impl From<u8> for Exception {
+    fn from(code: u8) -> Self {
+        match code {
+            0x01 => Exception::IllegalFunction,
+            0x02 => Exception::IllegalDataAddress,
+            0x03 => Exception::IllegalDataValue,
+            0x04 => Exception::ServerDeviceFailure,
+            0x05 => Exception::Acknowledge,
+            0x06 => Exception::ServerDeviceBusy,
+            0x08 => Exception::MemoryParityError,
+            0x0A => Exception::GatewayPathUnavailable,
+            0x0B => Exception::GatewayTargetFailed,
+            code => Exception::Other(code),
+        }
+    }
+}
+
+/// CRC-16 of a Modbus frame, sent low byte first
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFF;
+    for &byte in data {
+        crc ^= u16::from(byte);
+        for _bit in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// Modbus RTU master
+pub struct Master<TX, RX, Timer, DE = ()>
+where
+    TX: OutputPin,
+    RX: InputPin,
+    Timer: CountDown + Periodic,
+{
+    serial: Serial<TX, RX, Timer, DE>,
+    frame_gap: u16,
+    response_timeout: u32,
+}
+
+impl<TX, RX, Timer, DE, E> Master<TX, RX, Timer, DE>
+where
+    TX: OutputPin<Error = E>,
+    RX: InputPin<Error = E>,
+    Timer: CountDown + Periodic,
+    DE: DriverControl<E>,
+{
+    /// Create instance, setting `serial` to 8E1 format
+    pub fn new(mut serial: Serial<TX, RX, Timer, DE>) -> Self {
+        serial.set_config(SerialConfig {
+            data_bits: DataBits::Eight,
+            parity: Parity::Even,
+            stop_bits: StopBits::One,
+        });
+        Master {
+            serial,
+            frame_gap: FRAME_GAP,
+            response_timeout: RESPONSE_TIMEOUT,
+        }
+    }
+
+    /// Set the parity of the slaves, even by default
+    ///
+    /// Without parity, two stop bits are used to keep 11-bit characters, as
+    /// required by the Modbus specification.
+    pub fn set_parity(&mut self, parity: Parity) {
+        let stop_bits = match parity {
+            Parity::None => StopBits::Two,
+            _ => StopBits::One,
+        };
+        self.serial.set_config(SerialConfig {
+            data_bits: DataBits::Eight,
+            parity,
+            stop_bits,
+        });
+    }
+
+    /// Set the silence between frames, 39 bit times by default
+    pub fn set_frame_gap(&mut self, bit_times: u16) {
+        self.frame_gap = bit_times;
+    }
+
+    /// Set the time to wait for the start of a response, 2000 bit times by
+    /// default
+    pub fn set_response_timeout(&mut self, bit_times: u32) {
+        self.response_timeout = bit_times;
+    }
+
+    /// Read coils (function code 1), up to 2000
+    pub fn read_coils(
+        &mut self,
+        slave: u8,
+        address: u16,
+        coils: &mut [bool],
+    ) -> Result<(), Error<E>> {
+        self.read_bits(slave, READ_COILS, address, coils)
+    }
+
+    /// Read discrete inputs (function code 2), up to 2000
+    pub fn read_discrete_inputs(
+        &mut self,
+        slave: u8,
+        address: u16,
+        inputs: &mut [bool],
+    ) -> Result<(), Error<E>> {
+        self.read_bits(slave, READ_DISCRETE_INPUTS, address, inputs)
+    }
+
+    /// Read holding registers (function code 3), up to 125
+    pub fn read_holding_registers(
+        &mut self,
+        slave: u8,
+        address: u16,
+        registers: &mut [u16],
+    ) -> Result<(), Error<E>> {
+        self.read_registers(slave, READ_HOLDING_REGISTERS, address, registers)
+    }
+
+    /// Read input registers (function code 4), up to 125
+    pub fn read_input_registers(
+        &mut self,
+        slave: u8,
+        address: u16,
+        registers: &mut [u16],
+    ) -> Result<(), Error<E>> {
+        self.read_registers(slave, READ_INPUT_REGISTERS, address, registers)
+    }
+
+    /// Write a single coil (function code 5)
+    pub fn write_single_coil(&mut self, slave: u8, address: u16, on: bool) -> Result<(), Error<E>> {
+        let mut request = [0; 6];
+        let value = if on { 0xFF00 } else { 0x0000 };
+        header(&mut request, slave, WRITE_SINGLE_COIL, address, value);
+        self.write(&request)
+    }
+
+    /// Write a single holding register (function code 6)
+    pub fn write_single_register(
+        &mut self,
+        slave: u8,
+        address: u16,
+        value: u16,
+    ) -> Result<(), Error<E>> {
+        let mut request = [0; 6];
+        header(&mut request, slave, WRITE_SINGLE_REGISTER, address, value);
+        self.write(&request)
+    }
+
+    /// Write multiple coils (function code 15), up to 1968
+    pub fn write_multiple_coils(
+        &mut self,
+        slave: u8,
+        address: u16,
+        coils: &[bool],
+    ) -> Result<(), Error<E>> {
+        if coils.is_empty() || coils.len() > 1968 {
+            return Err(Error::InvalidRequest);
+        }
+        let mut request = [0; MAX_FRAME];
+        let len = header(
+            &mut request,
+            slave,
+            WRITE_MULTIPLE_COILS,
+            address,
+            coils.len() as u16,
+        );
+        let byte_count = coils.len().div_ceil(8);
+        request[len] = byte_count as u8;
+        let data = &mut request[len + 1..len + 1 + byte_count];
+        for (i, &coil) in coils.iter().enumerate() {
+            if coil {
+                data[i / 8] |= 1 << (i % 8);
+            }
+        }
+        self.write(&request[..len + 1 + byte_count])
+    }
+
+    /// Write multiple holding registers (function code 16), up to 123
+    pub fn write_multiple_registers(
+        &mut self,
+        slave: u8,
+        address: u16,
+        registers: &[u16],
+    ) -> Result<(), Error<E>> {
+        if registers.is_empty() || registers.len() > 123 {
+            return Err(Error::InvalidRequest);
+        }
+        let mut request = [0; MAX_FRAME];
+        let len = header(
+            &mut request,
+            slave,
+            WRITE_MULTIPLE_REGISTERS,
+            address,
+            registers.len() as u16,
+        );
+        let byte_count = registers.len() * 2;
+        request[len] = byte_count as u8;
+        let data = &mut request[len + 1..len + 1 + byte_count];
+        for (bytes, register) in data.chunks_exact_mut(2).zip(registers) {
+            bytes.copy_from_slice(&register.to_be_bytes());
+        }
+        self.write(&request[..len + 1 + byte_count])
+    }
+
+    fn read_bits(
+        &mut self,
+        slave: u8,
+        function: u8,
+        address: u16,
+        bits: &mut [bool],
+    ) -> Result<(), Error<E>> {
+        if bits.is_empty() || bits.len() > 2000 || slave == BROADCAST {
+            return Err(Error::InvalidRequest);
+        }
+        let mut request = [0; 6];
+        header(&mut request, slave, function, address, bits.len() as u16);
+        let mut response = [0; MAX_FRAME];
+        let data = self.transact(&request, &mut response)?;
+        let data = byte_counted(data, bits.len().div_ceil(8))?;
+        for (i, bit) in bits.iter_mut().enumerate() {
+            *bit = data[i / 8] & (1 << (i % 8)) != 0;
+        }
+        Ok(())
+    }
+
+    fn read_registers(
+        &mut self,
+        slave: u8,
+        function: u8,
+        address: u16,
+        registers: &mut [u16],
+    ) -> Result<(), Error<E>> {
+        if registers.is_empty() || registers.len() > 125 || slave == BROADCAST {
+            return Err(Error::InvalidRequest);
+        }
+        let mut request = [0; 6];
+        header(
+            &mut request,
+            slave,
+            function,
+            address,
+            registers.len() as u16,
+        );
+        let mut response = [0; MAX_FRAME];
+        let data = self.transact(&request, &mut response)?;
+        let data = byte_counted(data, registers.len() * 2)?;
+        for (register, bytes) in registers.iter_mut().zip(data.chunks_exact(2)) {
+            *register = u16::from_be_bytes([bytes[0], bytes[1]]);
+        }
+        Ok(())
+    }
+
+    /// Send a write request, which the slave acknowledges by echoing its
+    /// start address and value or quantity
+    fn write(&mut self, request: &[u8]) -> Result<(), Error<E>> {
+        let mut response = [0; MAX_FRAME];
+        let data = self.transact(request, &mut response)?;
+        if request[0] != BROADCAST && data != &request[2..6] {
+            return Err(Error::InvalidResponse);
+        }
+        Ok(())
+    }
+
+    /// Send `request`, without its CRC, and receive the response into
+    /// `response`, returning its data after the function code
+    ///
+    /// Broadcast requests return no data once sent.
+    fn transact<'a>(
+        &mut self,
+        request: &[u8],
+        response: &'a mut [u8; MAX_FRAME],
+    ) -> Result<&'a [u8], Error<E>> {
+        self.serial.wait_for_bits(self.frame_gap);
+        for byte in request.iter().chain(&crc16(request).to_le_bytes()) {
+            block!(self.serial.write(*byte))?;
+        }
+        block!(Write::<u8>::flush(&mut self.serial))?;
+        if request[0] == BROADCAST {
+            return Ok(&[]);
+        }
+
+        let len = self.receive(response)?;
+        let frame = &response[..len];
+        if len < 4 {
+            return Err(Error::InvalidResponse);
+        }
+        let (frame, crc) = frame.split_at(len - 2);
+        if crc16(frame).to_le_bytes() != crc {
+            return Err(Error::Crc);
+        }
+        if frame[0] != request[0] {
+            return Err(Error::InvalidResponse);
+        }
+        if frame[1] == request[1] | 0x80 && frame.len() == 3 {
+            return Err(Error::Exception(frame[2].into()));
+        }
+        if frame[1] != request[1] {
+            return Err(Error::InvalidResponse);
+        }
+        Ok(&frame[2..])
+    }
+
+    /// Receive a frame into `frame` until the line stays idle for the frame
+    /// gap, returning its length
+    fn receive(&mut self, frame: &mut [u8; MAX_FRAME]) -> Result<usize, Error<E>> {
+        let mut len = 0;
+        let mut timeout = self.response_timeout;
+        loop {
+            let byte = match self.serial.read_timeout(timeout) {
+                Err(serial::Error::Timeout) if len == 0 => return Err(Error::NoResponse),
+                Err(serial::Error::Timeout) => return Ok(len),
+                result => result?,
+            };
+            if len == MAX_FRAME {
+                return Err(Error::InvalidResponse);
+            }
+            frame[len] = byte;
+            len += 1;
+            timeout = self.frame_gap.into();
+        }
+    }
+}
+
+/// Write the slave address, function code, start address and a quantity or
+/// value to `request`, returning the number of bytes written
+fn header(request: &mut [u8], slave: u8, function: u8, address: u16, value: u16) -> usize {
+    request[0] = slave;
+    request[1] = function;
+    request[2..4].copy_from_slice(&address.to_be_bytes());
+    request[4..6].copy_from_slice(&value.to_be_bytes());
+    6
+}
+
+/// Data of a read response, which starts with its byte count
+fn byte_counted<E>(data: &[u8], byte_count: usize) -> Result<&[u8], Error<E>> {
+    match data.split_first() {
+        Some((&count, data)) if usize::from(count) == byte_count && data.len() == byte_count => {
+            Ok(data)
+        }
+        _ => Err(Error::InvalidResponse),
+    }
+}
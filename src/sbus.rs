@@ -0,0 +1,168 @@
+//! SBUS receiver
+//!
+//! SBUS frames are received over a [`Serial`] device at 100000 baud in 8E2
+//! format, with inverted levels, so the timer runs at 100 kHz multiplied by
+//! the oversampling factor of the device. Only the RX pin is used, TX can be
+//! a [`NoTx`](crate::serial::NoTx).
+//!
+//! A frame is 25 bytes long: a `0x0F` header, 16 proportional channels of
+//! 11 bits each, a flags byte with 2 digital channels and the frame lost and
+//! failsafe flags, and a footer. Frames are sent every 7 or 14 ms, and as
+//! the header value may also appear in the channel data, the start of a
+//! frame is found from the pause preceding it.
+//!
+//! ```ignore
+//! let mut serial = Serial::new(NoTx::new(), rx_pin, timer);
+//! serial.set_oversampling(Oversampling::X16);
+//! let mut sbus = Sbus::new(serial);
+//! let frame = block!(sbus.receive())?;
+//! ```
+
+use crate::serial::{self, DataBits, Parity, Serial, SerialConfig, StopBits};
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal::serial::Read;
+use embedded_hal::timer::{CountDown, Periodic};
+
+/// Length of a frame
+pub const FRAME_LEN: usize = 25;
+
+/// Number of proportional channels
+pub const CHANNELS: usize = 16;
+
+/// First byte of a frame
+const HEADER: u8 = 0x0F;
+
+/// Bit times after which a pause ends a frame, 1 ms
+///
+/// The pause between frames is at least 4 ms.
+const FRAME_GAP: u32 = 100;
+
+/// SBUS error
+#[derive(Debug)]
+pub enum Error<E> {
+    /// Serial communication error
+    Serial(serial::Error<E>),
+    /// Frame was cut short, or had a wrong header or footer
+    Frame,
+}
+
+impl<E> From<serial::Error<E>> for Error<E> {
+    fn from(error: serial::Error<E>) -> Self {
+        Error::Serial(error)
+    }
+}
+
+/// Decoded SBUS frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    /// Proportional channels, from 0 to 2047
+    pub channels: [u16; CHANNELS],
+    /// Digital channels 17 and 18
+    pub digital: [bool; 2],
+    /// Whether the receiver lost the frame before this one
+    pub frame_lost: bool,
+    /// Whether the receiver lost the transmitter signal, with channels set
+    /// to their failsafe positions
+    pub failsafe: bool,
+}
+
+impl Frame {
+    /// Decode the 25 bytes of a frame
+    ///
+    /// Returns `None` if the header or the footer is wrong. Besides the
+    /// `0x00` footer of SBUS, the footers of SBUS2 telemetry slots are
+    /// accepted.
+    pub fn decode(bytes: &[u8; FRAME_LEN]) -> Option<Self> {
+        let footer = bytes[FRAME_LEN - 1];
+        if bytes[0] != HEADER || !(footer == 0x00 || footer & 0xCF == 0x04) {
+            return None;
+        }
+
+        // channels are packed least significant bit first
+        let mut channels = [0; CHANNELS];
+        let mut bits: u32 = 0;
+        let mut bit_count = 0;
+        let mut channel = 0;
+        for &byte in &bytes[1..23] {
+            bits |= u32::from(byte) << bit_count;
+            bit_count += 8;
+            if bit_count >= 11 {
+                channels[channel] = (bits & 0x7FF) as u16;
+                bits >>= 11;
+                bit_count -= 11;
+                channel += 1;
+            }
+        }
+
+        let flags = bytes[23];
+        Some(Frame {
+            channels,
+            digital: [flags & 0x01 != 0, flags & 0x02 != 0],
+            frame_lost: flags & 0x04 != 0,
+            failsafe: flags & 0x08 != 0,
+        })
+    }
+}
+
+/// SBUS receiver
+pub struct Sbus<TX, RX, Timer>
+where
+    TX: OutputPin,
+    RX: InputPin,
+    Timer: CountDown + Periodic,
+{
+    serial: Serial<TX, RX, Timer>,
+}
+
+impl<TX, RX, Timer, E> Sbus<TX, RX, Timer>
+where
+    TX: OutputPin<Error = E>,
+    RX: InputPin<Error = E>,
+    Timer: CountDown + Periodic,
+{
+    /// Create instance, setting `serial` to 8E2 with inverted RX
+    ///
+    /// Use [`set_inverted`](Self::set_inverted) to undo the inversion if the
+    /// receiver output goes through an inverter.
+    pub fn new(mut serial: Serial<TX, RX, Timer>) -> Self {
+        serial.set_config(SerialConfig {
+            data_bits: DataBits::Eight,
+            parity: Parity::Even,
+            stop_bits: StopBits::Two,
+        });
+        serial.set_rx_inverted(true);
+        Sbus { serial }
+    }
+
+    /// Set whether the line is idle low as sent by SBUS receivers, `true` by
+    /// default, or idle high behind an inverter
+    pub fn set_inverted(&mut self, inverted: bool) {
+        self.serial.set_rx_inverted(inverted);
+    }
+
+    /// Receive a frame
+    ///
+    /// Returns `WouldBlock` while the line is idle. The bytes following the
+    /// first one are read until the frame is complete, or until a pause ends
+    /// it, in which case `Error::Frame` is returned. This happens when
+    /// reception starts within a frame; the following frame is then received
+    /// from its start.
+    ///
+    /// Like [`Serial`]'s `read`, this must be polled at least once per timer
+    /// tick while waiting for data.
+    pub fn receive(&mut self) -> nb::Result<Frame, Error<E>> {
+        let mut bytes = [0; FRAME_LEN];
+        bytes[0] = match Read::<u8>::read(&mut self.serial) {
+            Ok(byte) => byte,
+            Err(nb::Error::Other(error)) => return Err(nb::Error::Other(error.into())),
+            Err(nb::Error::WouldBlock) => return Err(nb::Error::WouldBlock),
+        };
+        for byte in bytes[1..].iter_mut() {
+            *byte = match self.serial.read_timeout(FRAME_GAP) {
+                Err(serial::Error::Timeout) => return Err(nb::Error::Other(Error::Frame)),
+                result => result.map_err(Error::from)?,
+            };
+        }
+        Frame::decode(&bytes).ok_or(nb::Error::Other(Error::Frame))
+    }
+}
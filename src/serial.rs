@@ -5,23 +5,734 @@
 //! - Output GPIO pin for transmission (TX)
 //! - Input GPIO pin for reception (RX)
 //!
-//! The timer must be configured to twice the desired communication frequency.
+//! The timer must be configured to the desired baud rate multiplied by the
+//! [`Oversampling`] factor, which is 3 by default.
+//!
+//! The receiver waits for the falling edge of the start bit. With the default
+//! 3x or with 16x oversampling, each bit is taken by majority vote of three
+//! samples around its middle, and the baud rates of both ends may differ by a
+//! few percent. 2x oversampling does not sample in the middle of the bit: the
+//! edge is only known to within half a bit, so each bit is sampled once
+//! somewhere in its first half and both ends must run at the same baud rate.
+//!
+//! Frames are 8N1 by default. Other formats, from 5 to 9 data bits with
+//! optional parity and 1, 1.5 or 2 stop bits, are selected with
+//! [`SerialConfig`]. 9-bit words are read and written as `u16`. With 9 data
+//! bits, a multiprocessor mode skips words sent to other devices, see
+//! [`Serial::set_address`].
+//!
+//! Received frames are checked for framing, parity, noise and break
+//! conditions, reported as [`Error`] variants. Breaks are sent with
+//! [`Serial::send_break`].
+//!
+//! The [`interrupt`] module provides variants driven from interrupt handlers.
+//!
+//! Hardware flow control through CTS and RTS pins is enabled with
+//! [`Serial::with_flow_control`].
+//!
+//! A receive-only device takes a [`NoTx`] instead of a TX pin.
+//!
+//! Either direction can be inverted, for idle-low lines such as SBUS or RS-232
+//! levels without a transceiver.
+//!
+//! An RS-485 transceiver is switched to sending through its [`DriverEnable`]
+//! pin from the first word written until `flush` or the next read, see
+//! [`Serial::with_driver_enable`]. After `write`, `flush` must be called to
+//! free the bus.
+//!
+//! [`HalfDuplexSerial`] sends and receives on a single open-drain wire, with
+//! collision detection and optional suppression of its own echo.
+//!
+//! The baud rate of incoming data can be measured with
+//! [`Serial::detect_bit_period`], or measured and applied to the timer with
+//! [`Serial::autobaud`].
+//!
+//! Besides the `embedded-hal` serial traits, [`Serial`] implements
+//! `core::fmt::Write`, and the `embedded-io` `Read` and `Write` traits with
+//! the `embedded-io` feature.
+//!
+//! Reading returns `WouldBlock` while no start bit is present. Once a start bit
+//! is seen, the rest of the frame is received before returning.
+//! [`Serial::read_timeout`] gives up after a number of bit periods instead.
+//! As the start bit is taken to begin when `read` first sees the line low,
+//! `read` must be polled at least once per timer tick while waiting for data.
+//! When that is not possible, use the [`interrupt::Receiver`] instead.
 //!
 
-use embedded_hal::digital::v2::{InputPin, OutputPin};
+pub mod interrupt;
+pub mod queue;
+
+use core::convert::Infallible;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::Range;
+use embedded_hal::blocking::serial::write;
+use embedded_hal::digital::v2::{InputPin, OutputPin, PinState};
 use embedded_hal::serial;
 use embedded_hal::timer::{CountDown, Periodic};
 use nb::block;
 
 /// Serial communication error type
+///
+/// On reception errors other than `Bus`, the received word is discarded.
 #[derive(Debug)]
 pub enum Error<E> {
     /// Bus error
     Bus(E),
+    /// Stop bit was low
+    Framing,
+    /// Parity bit didn't match the data bits
+    Parity,
+    /// Samples of a bit disagreed, only detected with 3x or 16x oversampling
+    Noise,
+    /// Line was low for a whole frame, including the stop bit
+    ///
+    /// Reading resumes once the line has returned to idle.
+    Break,
+    /// No start bit was received in time
+    Timeout,
+    /// A word was received before the previous ones were consumed
+    Overrun,
+    /// Line level read back while sending differed from the bit sent
+    Collision,
+    /// Multiprocessor mode was used without [`DataBits::Nine`]
+    AddressMode,
+}
+
+/// Ninth data bit, marking addresses in multiprocessor mode
+const ADDRESS_MARK: u16 = 0x100;
+
+/// Word sent or received in one frame: `u8`, or `u16` for 9 data bits
+pub trait Word: Copy {
+    /// Word holding the data bits of a frame, truncated to the word size
+    fn from_data(data: u16) -> Self;
+
+    /// Data bits of a frame holding this word
+    fn into_data(self) -> u16;
+}
+
+impl Word for u8 {
+    fn from_data(data: u16) -> Self {
+        data as u8
+    }
+
+    fn into_data(self) -> u16 {
+        u16::from(self)
+    }
+}
+
+impl Word for u16 {
+    fn from_data(data: u16) -> Self {
+        data
+    }
+
+    fn into_data(self) -> u16 {
+        self
+    }
+}
+
+/// Method of measuring the bit period of incoming data, see
+/// [`Serial::detect_bit_period`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Autobaud {
+    /// Shortest of this many low pulses, assuming one of them is a single bit
+    ShortestLowPulse(u8),
+    /// Sync character `0x55`, made of alternating bits
+    Sync55,
+    /// Sync character `0x7F`
+    Sync7F,
+}
+
+impl Autobaud {
+    /// Number of falling edges from the start bit to the falling edge of
+    /// bit 7 of the sync character, 8 bit times later
+    fn sync_falling_edges(self) -> Option<u8> {
+        match self {
+            Autobaud::ShortestLowPulse(_) => None,
+            Autobaud::Sync55 => Some(5),
+            Autobaud::Sync7F => Some(2),
+        }
+    }
+}
+
+/// Number of timer ticks per bit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Oversampling {
+    /// Two ticks per bit, each bit is sampled once, in its first half
+    X2,
+    /// Three ticks per bit, each bit is the majority of three samples
+    X3,
+    /// Sixteen ticks per bit, each bit is the majority of the 7th, 8th and
+    /// 9th samples
+    X16,
+}
+
+impl Default for Oversampling {
+    /// Default oversampling: three ticks per bit
+    fn default() -> Self {
+        Oversampling::X3
+    }
+}
+
+impl Oversampling {
+    /// Number of timer ticks per bit
+    pub fn ticks_per_bit(self) -> u8 {
+        match self {
+            Oversampling::X2 => 2,
+            Oversampling::X3 => 3,
+            Oversampling::X16 => 16,
+        }
+    }
+
+    /// Ticks within a bit at which the line is sampled
+    fn sample_ticks(self) -> Range<u8> {
+        match self {
+            Oversampling::X2 => 0..1,
+            Oversampling::X3 => 0..3,
+            Oversampling::X16 => 6..9,
+        }
+    }
+
+    /// Ticks within a bit whose samples must agree with the majority, or
+    /// the frame is reported as noisy
+    ///
+    /// With 3x oversampling, the first and last samples of a bit fall on its
+    /// neighbours if the baud rates differ slightly, so only a middle sample
+    /// outvoted by the other two counts as noise.
+    fn noise_ticks(self) -> Range<u8> {
+        match self {
+            Oversampling::X3 => 1..2,
+            _ => self.sample_ticks(),
+        }
+    }
+
+    /// Ticks within the stop bit at which the line is sampled
+    ///
+    /// Sampling ends in the middle of the stop bit at the latest, so that
+    /// the start bit of a following frame is not missed if the receiver is
+    /// slightly slower than the sender.
+    fn stop_sample_ticks(self) -> Range<u8> {
+        match self {
+            Oversampling::X3 => 1..2,
+            _ => self.sample_ticks(),
+        }
+    }
+}
+
+/// Number of data bits in a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataBits {
+    /// 5 data bits
+    Five = 5,
+    /// 6 data bits
+    Six = 6,
+    /// 7 data bits
+    Seven = 7,
+    /// 8 data bits
+    Eight = 8,
+    /// 9 data bits, only fully available as `u16` words
+    Nine = 9,
+}
+
+/// Parity bit following the data bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    /// No parity bit
+    None,
+    /// Even number of ones in data and parity bits
+    Even,
+    /// Odd number of ones in data and parity bits
+    Odd,
+    /// Parity bit always high
+    Mark,
+    /// Parity bit always low
+    Space,
+}
+
+/// Duration of the stop bits ending a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopBits {
+    /// 1 stop bit
+    One,
+    /// 1.5 stop bits, rounded up to whole timer ticks
+    OneAndHalf,
+    /// 2 stop bits
+    Two,
+}
+
+/// Serial frame format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialConfig {
+    /// Number of data bits
+    pub data_bits: DataBits,
+    /// Parity bit
+    pub parity: Parity,
+    /// Stop bits
+    pub stop_bits: StopBits,
+}
+
+impl Default for SerialConfig {
+    /// Default frame format: 8N1
+    fn default() -> Self {
+        SerialConfig {
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+        }
+    }
+}
+
+impl SerialConfig {
+    /// Mask of the data bits in a word
+    fn data_mask(&self) -> u16 {
+        (1 << self.data_bits as u8) - 1
+    }
+
+    /// Level of the parity bit for `data`, if there is one
+    fn parity_bit(&self, data: u16) -> Option<bool> {
+        let odd_ones = (data & self.data_mask()).count_ones() % 2 == 1;
+        match self.parity {
+            Parity::None => None,
+            Parity::Even => Some(odd_ones),
+            Parity::Odd => Some(!odd_ones),
+            Parity::Mark => Some(true),
+            Parity::Space => Some(false),
+        }
+    }
+
+    /// Position of the first stop bit in a frame, after the start, data and
+    /// parity bits
+    fn stop_bit(&self) -> u8 {
+        let parity_bits = if self.parity == Parity::None { 0 } else { 1 };
+        1 + self.data_bits as u8 + parity_bits
+    }
+
+    /// Duration of the stop bits in timer ticks
+    fn stop_ticks(&self, oversampling: Oversampling) -> u16 {
+        let ticks_per_bit = u16::from(oversampling.ticks_per_bit());
+        match self.stop_bits {
+            StopBits::One => ticks_per_bit,
+            StopBits::OneAndHalf => (3 * ticks_per_bit).div_ceil(2),
+            StopBits::Two => 2 * ticks_per_bit,
+        }
+    }
+}
+
+/// Result of advancing a [`FrameDecoder`] by one tick
+#[derive(Debug)]
+enum Decoded<E> {
+    /// The frame is not complete yet
+    Pending,
+    /// The start bit was a glitch, the line is high again
+    FalseStart,
+    /// A complete frame was received
+    Frame(u16),
+    /// A complete frame was received with an error
+    Error(Error<E>),
+}
+
+/// Decoder for one received frame, advanced one timer tick at a time from the
+/// falling edge of the start bit
+struct FrameDecoder {
+    config: SerialConfig,
+    oversampling: Oversampling,
+    /// Tick within the current bit
+    tick: u8,
+    /// Current bit, 0 being the start bit
+    bit: u8,
+    /// High samples of the current bit
+    ones: u8,
+    /// High samples of the current bit within the noise detection ticks
+    noise_ones: u8,
+    data: u16,
+    /// Whether any bit read so far was high
+    any_high: bool,
+    /// Whether the samples of any bit disagreed
+    noise: bool,
+    parity_error: bool,
+}
+
+impl FrameDecoder {
+    fn new(config: SerialConfig, oversampling: Oversampling) -> Self {
+        FrameDecoder {
+            config,
+            oversampling,
+            tick: 0,
+            bit: 0,
+            ones: 0,
+            noise_ones: 0,
+            data: 0,
+            any_high: false,
+            noise: false,
+            parity_error: false,
+        }
+    }
+
+    /// Ticks within the current bit at which the line is sampled
+    fn sample_ticks(&self) -> Range<u8> {
+        if self.bit == self.config.stop_bit() {
+            self.oversampling.stop_sample_ticks()
+        } else {
+            self.oversampling.sample_ticks()
+        }
+    }
+
+    /// Whether the line level must be sampled on the current tick
+    fn is_sampling(&self) -> bool {
+        self.sample_ticks().contains(&self.tick)
+    }
+
+    /// Advance by one tick, `is_high` being the line level when
+    /// [`is_sampling`](Self::is_sampling)
+    fn tick<E>(&mut self, is_high: bool) -> Decoded<E> {
+        let samples = self.sample_ticks();
+        let noise_ticks = self.oversampling.noise_ticks();
+        if samples.contains(&self.tick) && is_high {
+            self.ones += 1;
+            if noise_ticks.contains(&self.tick) {
+                self.noise_ones += 1;
+            }
+        }
+
+        let mut decoded = Decoded::Pending;
+        if self.tick + 1 == samples.end {
+            let bit_high = usize::from(self.ones) * 2 > samples.len();
+            let noise_samples = samples.filter(|tick| noise_ticks.contains(tick)).count();
+            let noise_high = usize::from(self.noise_ones);
+            self.noise |= if bit_high {
+                noise_high != noise_samples
+            } else {
+                noise_high != 0
+            };
+            self.any_high |= bit_high;
+            self.ones = 0;
+            self.noise_ones = 0;
+
+            let data_bits = self.config.data_bits as u8;
+            match self.bit {
+                0 if bit_high => decoded = Decoded::FalseStart,
+                0 => {}
+                bit if bit <= data_bits => self.data |= u16::from(bit_high) << (bit - 1),
+                bit if bit == self.config.stop_bit() => decoded = self.frame(bit_high),
+                _ => self.parity_error = self.config.parity_bit(self.data) != Some(bit_high),
+            }
+        }
+
+        self.tick += 1;
+        if self.tick == self.oversampling.ticks_per_bit() {
+            self.tick = 0;
+            self.bit += 1;
+        }
+        decoded
+    }
+
+    /// Check the complete frame once its stop bit is known
+    fn frame<E>(&self, stop_bit_high: bool) -> Decoded<E> {
+        if !self.any_high {
+            Decoded::Error(Error::Break)
+        } else if !stop_bit_high {
+            Decoded::Error(Error::Framing)
+        } else if self.parity_error {
+            Decoded::Error(Error::Parity)
+        } else if self.noise {
+            Decoded::Error(Error::Noise)
+        } else {
+            Decoded::Frame(self.data)
+        }
+    }
+}
+
+/// Encoder for one transmitted frame, advanced one timer tick at a time from
+/// the start bit
+struct FrameEncoder {
+    config: SerialConfig,
+    oversampling: Oversampling,
+    data: u16,
+    /// Tick within the current bit
+    tick: u16,
+    /// Current bit, 0 being the start bit
+    bit: u8,
+}
+
+impl FrameEncoder {
+    /// Encoder for a frame holding the lowest data bits of `word`
+    fn new(config: SerialConfig, oversampling: Oversampling, word: u16) -> Self {
+        FrameEncoder {
+            config,
+            oversampling,
+            data: word & config.data_mask(),
+            tick: 0,
+            bit: 0,
+        }
+    }
+
+    /// Whether the line must be driven to [`level`](Self::level) on the
+    /// current tick
+    fn is_bit_start(&self) -> bool {
+        self.tick == 0 && !self.is_complete()
+    }
+
+    /// Whether the current tick is in the middle of the current bit, where
+    /// the line level can be read back once it has settled
+    fn is_mid_bit(&self) -> bool {
+        self.tick == self.bit_ticks() / 2
+    }
+
+    /// Line level of the current bit
+    fn level(&self) -> bool {
+        let data_bits = self.config.data_bits as u8;
+        match self.bit {
+            0 => false,
+            bit if bit <= data_bits => (self.data >> (bit - 1)) & 1 == 1,
+            bit if bit == self.config.stop_bit() => true,
+            _ => self.config.parity_bit(self.data).unwrap_or(true),
+        }
+    }
+
+    /// Whether the stop bits are over
+    fn is_complete(&self) -> bool {
+        self.bit > self.config.stop_bit()
+    }
+
+    /// Number of ticks of the current bit
+    fn bit_ticks(&self) -> u16 {
+        if self.bit == self.config.stop_bit() {
+            self.config.stop_ticks(self.oversampling)
+        } else {
+            u16::from(self.oversampling.ticks_per_bit())
+        }
+    }
+
+    /// Advance by one tick
+    fn tick(&mut self) {
+        self.tick += 1;
+        if self.tick == self.bit_ticks() {
+            self.tick = 0;
+            self.bit += 1;
+        }
+    }
+}
+
+/// Control of an external line driver while sending
+///
+/// Implemented by `()` for no control, and by [`DriverEnable`].
+pub trait DriverControl<E> {
+    /// Enable the driver before the start bit
+    fn enable(&mut self) -> Result<(), E>;
+
+    /// Disable the driver after the stop bits of the last word
+    fn disable(&mut self) -> Result<(), E>;
+
+    /// Bit times to wait between enabling the driver and the start bit
+    fn pre_delay(&self) -> u8;
+
+    /// Bit times to wait between the last stop bits and disabling the driver
+    fn post_delay(&self) -> u8;
+}
+
+impl<E> DriverControl<E> for () {
+    fn enable(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn disable(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn pre_delay(&self) -> u8 {
+        0
+    }
+
+    fn post_delay(&self) -> u8 {
+        0
+    }
+}
+
+/// Driver enable pin of an RS-485 transceiver, tied to its receiver enable
+/// pin
+///
+/// The driver is enabled while sending, and the receiver the rest of the time.
+pub struct DriverEnable<Pin>
+where
+    Pin: OutputPin,
+{
+    pin: Pin,
+    active: PinState,
+    pre_delay: u8,
+    post_delay: u8,
+}
+
+impl<Pin, E> DriverEnable<Pin>
+where
+    Pin: OutputPin<Error = E>,
+{
+    /// Create instance, the driver being enabled when the pin is at the
+    /// `active` level
+    pub fn new(pin: Pin, active: PinState) -> Self {
+        DriverEnable {
+            pin,
+            active,
+            pre_delay: 0,
+            post_delay: 0,
+        }
+    }
+
+    /// Set the bit times waited after enabling the driver before the first
+    /// start bit, and after the last stop bits before disabling it
+    pub fn set_delays(&mut self, pre_delay: u8, post_delay: u8) {
+        self.pre_delay = pre_delay;
+        self.post_delay = post_delay;
+    }
+}
+
+impl<Pin, E> DriverControl<E> for DriverEnable<Pin>
+where
+    Pin: OutputPin<Error = E>,
+{
+    fn enable(&mut self) -> Result<(), E> {
+        self.pin.set_state(self.active)
+    }
+
+    fn disable(&mut self) -> Result<(), E> {
+        self.pin.set_state(!self.active)
+    }
+
+    fn pre_delay(&self) -> u8 {
+        self.pre_delay
+    }
+
+    fn post_delay(&self) -> u8 {
+        self.post_delay
+    }
+}
+
+/// Clear to send (CTS) input, checked before sending each word
+///
+/// Implemented by `()` for no flow control, and by [`Cts`].
+pub trait ClearToSend<E> {
+    /// Whether the peer can receive a word
+    fn is_clear(&self) -> Result<bool, E>;
+}
+
+impl<E> ClearToSend<E> for () {
+    fn is_clear(&self) -> Result<bool, E> {
+        Ok(true)
+    }
+}
+
+/// Request to send (RTS) output, telling the peer whether words can be
+/// received
+///
+/// Implemented by `()` for no flow control, and by [`Rts`].
+pub trait RequestToSend<E> {
+    /// Assert or deassert the output
+    fn set_ready(&mut self, ready: bool) -> Result<(), E>;
+}
+
+impl<E> RequestToSend<E> for () {
+    fn set_ready(&mut self, _ready: bool) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+/// Clear to send input pin
+pub struct Cts<Pin>
+where
+    Pin: InputPin,
+{
+    pin: Pin,
+    active: PinState,
+}
+
+impl<Pin, E> Cts<Pin>
+where
+    Pin: InputPin<Error = E>,
+{
+    /// Create instance, the peer being ready when the pin is at the `active`
+    /// level, usually low
+    pub fn new(pin: Pin, active: PinState) -> Self {
+        Cts { pin, active }
+    }
+}
+
+impl<Pin, E> ClearToSend<E> for Cts<Pin>
+where
+    Pin: InputPin<Error = E>,
+{
+    fn is_clear(&self) -> Result<bool, E> {
+        Ok(self.pin.is_high()? == (self.active == PinState::High))
+    }
+}
+
+/// Request to send output pin
+pub struct Rts<Pin>
+where
+    Pin: OutputPin,
+{
+    pin: Pin,
+    active: PinState,
+}
+
+impl<Pin, E> Rts<Pin>
+where
+    Pin: OutputPin<Error = E>,
+{
+    /// Create instance, the pin being at the `active` level, usually low,
+    /// while words can be received
+    pub fn new(pin: Pin, active: PinState) -> Self {
+        Rts { pin, active }
+    }
+}
+
+impl<Pin, E> RequestToSend<E> for Rts<Pin>
+where
+    Pin: OutputPin<Error = E>,
+{
+    fn set_ready(&mut self, ready: bool) -> Result<(), E> {
+        if ready {
+            self.pin.set_state(self.active)
+        } else {
+            self.pin.set_state(!self.active)
+        }
+    }
+}
+
+/// TX pin of a receive-only [`Serial`], driving nothing
+///
+/// Its error type follows the RX pin, so that no spare pin is needed when
+/// only receiving, e.g. with [`Sbus`](crate::sbus::Sbus).
+pub struct NoTx<E = Infallible>(PhantomData<fn() -> E>);
+
+impl<E> NoTx<E> {
+    /// Create instance
+    pub fn new() -> Self {
+        NoTx(PhantomData)
+    }
+}
+
+impl<E> Default for NoTx<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> OutputPin for NoTx<E> {
+    type Error = E;
+
+    fn set_low(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), E> {
+        Ok(())
+    }
 }
 
 /// Bit banging serial communication (USART) device
-pub struct Serial<TX, RX, Timer>
+///
+/// `DE` controls an external line driver, such as an RS-485 transceiver, and
+/// `CTS` and `RTS` are flow control signals. Each is `()` when unused.
+pub struct Serial<TX, RX, Timer, DE = (), CTS = (), RTS = ()>
 where
     TX: OutputPin,
     RX: InputPin,
@@ -30,6 +741,23 @@ where
     tx: TX,
     rx: RX,
     timer: Timer,
+    de: DE,
+    /// Whether the line driver is enabled
+    driving: bool,
+    cts: CTS,
+    rts: RTS,
+    /// Bit times to wait for CTS before giving up, if any
+    cts_timeout: Option<u32>,
+    config: SerialConfig,
+    oversampling: Oversampling,
+    tx_inverted: bool,
+    rx_inverted: bool,
+    /// Whether the line is still low after a break
+    in_break: bool,
+    /// Own address in multiprocessor mode
+    address: Option<u8>,
+    /// Whether the last address received was our own
+    addressed: bool,
 }
 
 impl<TX, RX, Timer, E> Serial<TX, RX, Timer>
@@ -40,7 +768,329 @@ where
 {
     /// Create instance
     pub fn new(tx: TX, rx: RX, timer: Timer) -> Self {
-        Serial { tx, rx, timer }
+        Serial::with_parts(tx, rx, timer, (), (), ())
+    }
+}
+
+impl<TX, RX, Timer, Pin, E> Serial<TX, RX, Timer, DriverEnable<Pin>>
+where
+    TX: OutputPin<Error = E>,
+    RX: InputPin<Error = E>,
+    Timer: CountDown + Periodic,
+    Pin: OutputPin<Error = E>,
+{
+    /// Create instance raising the driver enable pin `de` of an RS-485
+    /// transceiver while sending
+    ///
+    /// The driver is enabled before the first word written, and stays enabled
+    /// across consecutive words until `flush`, or until the next read. After
+    /// `write` or the blocking `bwrite_all`, `flush` or `bflush` must be
+    /// called to free the bus. `core::fmt::Write` releases the driver itself
+    /// at the end of each string.
+    pub fn with_driver_enable(
+        tx: TX,
+        rx: RX,
+        timer: Timer,
+        mut de: DriverEnable<Pin>,
+    ) -> Result<Self, crate::serial::Error<E>> {
+        de.disable().map_err(Error::Bus)?;
+        Ok(Serial::with_parts(tx, rx, timer, de, (), ()))
+    }
+}
+
+impl<TX, RX, Timer, CTS, RTS, E> Serial<TX, RX, Timer, (), CTS, RTS>
+where
+    TX: OutputPin<Error = E>,
+    RX: InputPin<Error = E>,
+    Timer: CountDown + Periodic,
+    CTS: ClearToSend<E>,
+    RTS: RequestToSend<E>,
+{
+    /// Create instance with hardware flow control
+    ///
+    /// Each word is sent once `cts` is asserted. `rts` is asserted while
+    /// reading, and deasserted from the reception of a word until the next
+    /// read. Either may be `()` if unused.
+    pub fn with_flow_control(
+        tx: TX,
+        rx: RX,
+        timer: Timer,
+        cts: CTS,
+        mut rts: RTS,
+    ) -> Result<Self, crate::serial::Error<E>> {
+        rts.set_ready(false).map_err(Error::Bus)?;
+        Ok(Serial::with_parts(tx, rx, timer, (), cts, rts))
+    }
+}
+
+impl<TX, RX, Timer, DE, CTS, RTS, E> Serial<TX, RX, Timer, DE, CTS, RTS>
+where
+    TX: OutputPin<Error = E>,
+    RX: InputPin<Error = E>,
+    Timer: CountDown + Periodic,
+    DE: DriverControl<E>,
+    CTS: ClearToSend<E>,
+    RTS: RequestToSend<E>,
+{
+    fn with_parts(tx: TX, rx: RX, timer: Timer, de: DE, cts: CTS, rts: RTS) -> Self {
+        Serial {
+            tx,
+            rx,
+            timer,
+            de,
+            driving: false,
+            cts,
+            rts,
+            cts_timeout: None,
+            config: SerialConfig::default(),
+            oversampling: Oversampling::default(),
+            tx_inverted: false,
+            rx_inverted: false,
+            in_break: false,
+            address: None,
+            addressed: false,
+        }
+    }
+
+    /// Set the frame format
+    pub fn set_config(&mut self, config: SerialConfig) {
+        self.config = config;
+    }
+
+    /// Set the number of timer ticks per bit
+    ///
+    /// The timer frequency must be adjusted accordingly.
+    pub fn set_oversampling(&mut self, oversampling: Oversampling) {
+        self.oversampling = oversampling;
+    }
+
+    /// Set the number of bit times to wait for CTS before each word, after
+    /// which writing fails with `Error::Timeout`, or `None` to wait forever
+    pub fn set_cts_timeout(&mut self, bit_periods: Option<u32>) {
+        self.cts_timeout = bit_periods;
+    }
+
+    /// Set whether TX is idle low, with every level sent inverted
+    ///
+    /// TX is driven to the resulting idle level.
+    pub fn set_tx_inverted(&mut self, inverted: bool) -> Result<(), crate::serial::Error<E>> {
+        self.tx_inverted = inverted;
+        self.set_tx(true)
+    }
+
+    /// Set whether RX is idle low, with every level received inverted
+    pub fn set_rx_inverted(&mut self, inverted: bool) {
+        self.rx_inverted = inverted;
+    }
+
+    /// Set the own address in multiprocessor mode, or `None` to leave it
+    ///
+    /// In multiprocessor mode, with [`DataBits::Nine`], words with the ninth
+    /// bit set are addresses. Only the words from an address matching our own
+    /// until the next address are read, including that address; others are
+    /// skipped. Reading returns `Error::AddressMode` while an address is set
+    /// with another number of data bits.
+    pub fn set_address(&mut self, address: Option<u8>) {
+        self.address = address;
+        self.addressed = false;
+    }
+
+    /// Send `address` with the ninth bit set, to select a device in
+    /// multiprocessor mode
+    ///
+    /// Returns `Error::AddressMode` without sending anything unless the frame
+    /// format has [`DataBits::Nine`].
+    pub fn write_address(&mut self, address: u8) -> Result<(), crate::serial::Error<E>> {
+        if self.config.data_bits != DataBits::Nine {
+            return Err(Error::AddressMode);
+        }
+        self.write_frame(ADDRESS_MARK | u16::from(address))
+    }
+
+    /// Measure the bit period of incoming data in timer ticks
+    ///
+    /// The timer should run much faster than the expected baud rate, as the
+    /// line is sampled once per tick. A sync character must start while the
+    /// line is idle, and is consumed. Returns `Error::Timeout` if the
+    /// measurement isn't complete after `timeout` ticks.
+    pub fn detect_bit_period(
+        &mut self,
+        method: Autobaud,
+        timeout: u32,
+    ) -> Result<u32, crate::serial::Error<E>> {
+        self.disable_driver()?;
+        let mut was_high = self.rx_is_high()?;
+        let mut fell_at = None;
+        let mut first_fall = 0;
+        let mut falls = 0;
+        let mut pulses = 0;
+        let mut shortest = u32::MAX;
+        let mut synced = None;
+
+        for tick in 0..timeout {
+            self.wait_for_timer();
+            let is_high = self.rx_is_high()?;
+            if was_high && !is_high {
+                fell_at = Some(tick);
+                if falls == 0 {
+                    first_fall = tick;
+                }
+                falls += 1;
+                if Some(falls) == method.sync_falling_edges() {
+                    synced = Some((tick - first_fall + 4) / 8);
+                }
+            } else if !was_high && is_high {
+                // let the sync character end before returning
+                if let Some(bit_period) = synced {
+                    return Ok(bit_period);
+                }
+                if let (Some(fell_at), Autobaud::ShortestLowPulse(count)) = (fell_at, method) {
+                    shortest = shortest.min(tick - fell_at);
+                    pulses += 1;
+                    if pulses >= count {
+                        return Ok(shortest);
+                    }
+                }
+            }
+            was_high = is_high;
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Measure the bit period of incoming data, then restart the timer for it
+    ///
+    /// `count` converts the bit period in ticks of the timer as currently
+    /// started into its new count, which must account for the oversampling
+    /// factor. Returns the bit period as measured.
+    pub fn autobaud<T>(
+        &mut self,
+        method: Autobaud,
+        timeout: u32,
+        count: impl FnOnce(u32) -> T,
+    ) -> Result<u32, crate::serial::Error<E>>
+    where
+        T: Into<Timer::Time>,
+    {
+        let bit_period = self.detect_bit_period(method, timeout)?;
+        self.timer.start(count(bit_period));
+        Ok(bit_period)
+    }
+
+    /// Read a word, waiting at most `bit_periods` bit times for its start bit
+    ///
+    /// Returns `Error::Timeout` if the line stays idle, e.g. because the peer
+    /// went silent.
+    pub fn read_timeout<W>(&mut self, bit_periods: u32) -> Result<W, crate::serial::Error<E>>
+    where
+        Self: serial::Read<W, Error = crate::serial::Error<E>>,
+    {
+        let timeout = bit_periods.saturating_mul(u32::from(self.oversampling.ticks_per_bit()));
+        let mut ticks = 0;
+        loop {
+            match serial::Read::read(self) {
+                Ok(word) => return Ok(word),
+                Err(nb::Error::Other(error)) => return Err(error),
+                Err(nb::Error::WouldBlock) => {}
+            }
+
+            if self.timer.wait().is_ok() {
+                ticks += 1;
+                if ticks >= timeout {
+                    return Err(Error::Timeout);
+                }
+            }
+        }
+    }
+
+    /// Send a break, holding TX low for `bit_times` bit times, then idle for
+    /// one bit time so that a following start bit can be told apart
+    ///
+    /// Breaks are used e.g. by LIN, DMX512 and bootloaders to mark the start
+    /// of a message. They must last longer than a frame to be told apart from
+    /// a `0x00` word.
+    pub fn send_break(&mut self, bit_times: u16) -> Result<(), crate::serial::Error<E>> {
+        self.enable_driver()?;
+        self.set_tx(false)?;
+        self.wait_for_bits(bit_times);
+        self.set_tx(true)?;
+        self.wait_for_bits(1);
+        Ok(())
+    }
+
+    /// Send one frame holding the lowest data bits of `word`, with the line
+    /// driver enabled
+    fn write_frame(&mut self, word: u16) -> Result<(), crate::serial::Error<E>> {
+        self.wait_for_cts()?;
+        self.enable_driver()?;
+
+        let mut encoder = FrameEncoder::new(self.config, self.oversampling, word);
+        while !encoder.is_complete() {
+            if encoder.is_bit_start() {
+                self.set_tx(encoder.level())?;
+            }
+            self.wait_for_timer();
+            encoder.tick();
+        }
+        Ok(())
+    }
+
+    /// Enable the line driver unless it already is
+    fn enable_driver(&mut self) -> Result<(), crate::serial::Error<E>> {
+        if !self.driving {
+            self.de.enable().map_err(Error::Bus)?;
+            self.wait_for_bits(self.de.pre_delay().into());
+            self.driving = true;
+        }
+        Ok(())
+    }
+
+    /// Disable the line driver once the last word sent is over
+    fn disable_driver(&mut self) -> Result<(), crate::serial::Error<E>> {
+        if self.driving {
+            self.wait_for_bits(self.de.post_delay().into());
+            self.de.disable().map_err(Error::Bus)?;
+            self.driving = false;
+        }
+        Ok(())
+    }
+
+    /// Wait until the peer is clear to receive a word
+    fn wait_for_cts(&mut self) -> Result<(), crate::serial::Error<E>> {
+        let timeout = self.cts_timeout.map(|bit_periods| {
+            bit_periods.saturating_mul(u32::from(self.oversampling.ticks_per_bit()))
+        });
+        let mut ticks = 0;
+        while !self.cts.is_clear().map_err(Error::Bus)? {
+            if Some(ticks) == timeout {
+                return Err(Error::Timeout);
+            }
+            self.wait_for_timer();
+            ticks += 1;
+        }
+        Ok(())
+    }
+
+    /// Drive TX to the level of a `high` bit, inverted if configured so
+    #[inline]
+    fn set_tx(&mut self, high: bool) -> Result<(), crate::serial::Error<E>> {
+        if high != self.tx_inverted {
+            self.tx.set_high().map_err(Error::Bus)
+        } else {
+            self.tx.set_low().map_err(Error::Bus)
+        }
+    }
+
+    /// Whether RX is at the level of a `high` bit, inverted if configured so
+    #[inline]
+    fn rx_is_high(&self) -> Result<bool, crate::serial::Error<E>> {
+        Ok(self.rx.is_high().map_err(Error::Bus)? != self.rx_inverted)
+    }
+
+    #[inline]
+    pub(crate) fn wait_for_bits(&mut self, bits: u16) {
+        for _tick in 0..u32::from(bits) * u32::from(self.oversampling.ticks_per_bit()) {
+            self.wait_for_timer();
+        }
     }
 
     #[inline]
@@ -49,59 +1099,425 @@ where
     }
 }
 
-impl<TX, RX, Timer, E> serial::Write<u8> for Serial<TX, RX, Timer>
+impl<TX, RX, Timer, DE, CTS, RTS, E, W> serial::Write<W> for Serial<TX, RX, Timer, DE, CTS, RTS>
 where
     TX: OutputPin<Error = E>,
     RX: InputPin<Error = E>,
     Timer: CountDown + Periodic,
+    DE: DriverControl<E>,
+    CTS: ClearToSend<E>,
+    RTS: RequestToSend<E>,
+    W: Word,
 {
     type Error = crate::serial::Error<E>;
 
-    fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
-        let mut data_out = byte;
-        self.tx.set_low().map_err(Error::Bus)?; // start bit
-        self.wait_for_timer();
-        for _bit in 0..8 {
-            if data_out & 1 == 1 {
-                self.tx.set_high().map_err(Error::Bus)?;
-            } else {
-                self.tx.set_low().map_err(Error::Bus)?;
-            }
-            data_out >>= 1;
-            self.wait_for_timer();
-        }
-        self.tx.set_high().map_err(Error::Bus)?; // stop bit
-        self.wait_for_timer();
+    fn write(&mut self, word: W) -> nb::Result<(), Self::Error> {
+        self.write_frame(word.into_data())?;
         Ok(())
     }
 
+    /// Disable the line driver once the last word sent is over
     fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.disable_driver()?;
         Ok(())
     }
 }
 
-impl<TX, RX, Timer, E> serial::Read<u8> for Serial<TX, RX, Timer>
+impl<TX, RX, Timer, DE, CTS, RTS, E, W> serial::Read<W> for Serial<TX, RX, Timer, DE, CTS, RTS>
 where
     TX: OutputPin<Error = E>,
     RX: InputPin<Error = E>,
     Timer: CountDown + Periodic,
+    DE: DriverControl<E>,
+    CTS: ClearToSend<E>,
+    RTS: RequestToSend<E>,
+    W: Word,
 {
     type Error = crate::serial::Error<E>;
 
-    fn read(&mut self) -> nb::Result<u8, Self::Error> {
-        let mut data_in = 0;
-        // wait for start bit
-        while self.rx.is_high().map_err(Error::Bus)? {}
-        self.wait_for_timer();
-        for _bit in 0..8 {
-            data_in <<= 1;
-            if self.rx.is_high().map_err(Error::Bus)? {
-                data_in |= 1
+    /// Read a frame, dropping the ninth data bit if `W` is `u8`
+    ///
+    /// The line driver is disabled first if it is still enabled.
+    ///
+    /// The start bit is taken to begin when this first sees the line low, so
+    /// bits are sampled late by the time elapsed since the falling edge. Call
+    /// it at least once per timer tick while waiting for data, or use the
+    /// [`interrupt::Receiver`] when the polling rate is not under control.
+    fn read(&mut self) -> nb::Result<W, Self::Error> {
+        if self.address.is_some() && self.config.data_bits != DataBits::Nine {
+            return Err(nb::Error::Other(Error::AddressMode));
+        }
+        self.disable_driver()?;
+        self.rts.set_ready(true).map_err(Error::Bus)?;
+        let result = read_frame(
+            &mut self.rx,
+            &mut self.timer,
+            self.config,
+            self.oversampling,
+            self.rx_inverted,
+            &mut self.in_break,
+        );
+        if !matches!(result, Err(nb::Error::WouldBlock)) {
+            self.rts.set_ready(false).map_err(Error::Bus)?;
+        }
+
+        let data = result?;
+        if let Some(address) = self.address {
+            if data & ADDRESS_MARK != 0 {
+                self.addressed = data & 0xFF == u16::from(address);
             }
-            self.wait_for_timer();
+            if !self.addressed {
+                return Err(nb::Error::WouldBlock);
+            }
+        }
+        Ok(W::from_data(data))
+    }
+}
+
+impl<TX, RX, Timer, DE, CTS, RTS, E, W> write::Default<W> for Serial<TX, RX, Timer, DE, CTS, RTS>
+where
+    TX: OutputPin<Error = E>,
+    RX: InputPin<Error = E>,
+    Timer: CountDown + Periodic,
+    DE: DriverControl<E>,
+    CTS: ClearToSend<E>,
+    RTS: RequestToSend<E>,
+    W: Word,
+{
+}
+
+impl<TX, RX, Timer, DE, CTS, RTS, E> fmt::Write for Serial<TX, RX, Timer, DE, CTS, RTS>
+where
+    TX: OutputPin<Error = E>,
+    RX: InputPin<Error = E>,
+    Timer: CountDown + Periodic,
+    DE: DriverControl<E>,
+    CTS: ClearToSend<E>,
+    RTS: RequestToSend<E>,
+{
+    /// Write the bytes of `s`, then disable the line driver once the last one
+    /// is over
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.write_frame(byte.into()).map_err(|_| fmt::Error)?;
+        }
+        self.disable_driver().map_err(|_| fmt::Error)
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<E: fmt::Debug> embedded_io::Error for Error<E> {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self {
+            Error::Framing | Error::Parity | Error::Noise | Error::Break => {
+                embedded_io::ErrorKind::InvalidData
+            }
+            Error::Timeout => embedded_io::ErrorKind::TimedOut,
+            Error::AddressMode => embedded_io::ErrorKind::InvalidInput,
+            Error::Bus(_) | Error::Overrun | Error::Collision => embedded_io::ErrorKind::Other,
+        }
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<TX, RX, Timer, DE, CTS, RTS, E> embedded_io::ErrorType for Serial<TX, RX, Timer, DE, CTS, RTS>
+where
+    TX: OutputPin<Error = E>,
+    RX: InputPin<Error = E>,
+    Timer: CountDown + Periodic,
+    E: fmt::Debug,
+{
+    type Error = crate::serial::Error<E>;
+}
+
+#[cfg(feature = "embedded-io")]
+impl<TX, RX, Timer, DE, CTS, RTS, E> embedded_io::Read for Serial<TX, RX, Timer, DE, CTS, RTS>
+where
+    TX: OutputPin<Error = E>,
+    RX: InputPin<Error = E>,
+    Timer: CountDown + Periodic,
+    DE: DriverControl<E>,
+    CTS: ClearToSend<E>,
+    RTS: RequestToSend<E>,
+    E: fmt::Debug,
+{
+    /// Wait for a first byte, then read the bytes following it, each starting
+    /// within two bit times of the previous one
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let (first, rest) = match buf.split_first_mut() {
+            Some(split) => split,
+            None => return Ok(0),
+        };
+        *first = block!(serial::Read::read(self))?;
+
+        let mut count = 1;
+        for byte in rest {
+            match self.read_timeout(2) {
+                Ok(word) => *byte = word,
+                Err(Error::Timeout) => break,
+                Err(error) => return Err(error),
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<TX, RX, Timer, DE, CTS, RTS, E> embedded_io::Write for Serial<TX, RX, Timer, DE, CTS, RTS>
+where
+    TX: OutputPin<Error = E>,
+    RX: InputPin<Error = E>,
+    Timer: CountDown + Periodic,
+    DE: DriverControl<E>,
+    CTS: ClearToSend<E>,
+    RTS: RequestToSend<E>,
+    E: fmt::Debug,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        for byte in buf {
+            self.write_frame((*byte).into())?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.disable_driver()
+    }
+}
+
+/// Receive a frame if its start bit has begun, from an idle low line if
+/// `inverted`
+///
+/// After a break, `in_break` is set until the line returns to idle, so that
+/// the rest of the break isn't taken for another frame.
+fn read_frame<RX, Timer, E>(
+    rx: &mut RX,
+    timer: &mut Timer,
+    config: SerialConfig,
+    oversampling: Oversampling,
+    inverted: bool,
+    in_break: &mut bool,
+) -> nb::Result<u16, crate::serial::Error<E>>
+where
+    RX: InputPin<Error = E>,
+    Timer: CountDown + Periodic,
+{
+    if rx.is_high().map_err(Error::Bus)? != inverted {
+        *in_break = false;
+        return Err(nb::Error::WouldBlock);
+    }
+    if *in_break {
+        return Err(nb::Error::WouldBlock);
+    }
+    // the stop bit is sampled in its middle, so that the next start bit
+    // is found even if the sender is slightly faster
+    match receive_frame(rx, timer, config, oversampling, inverted) {
+        Ok(Some(data)) => Ok(data),
+        Ok(None) => Err(nb::Error::WouldBlock),
+        Err(Error::Break) => {
+            *in_break = true;
+            Err(nb::Error::Other(Error::Break))
+        }
+        Err(error) => Err(nb::Error::Other(error)),
+    }
+}
+
+/// Receive a frame whose start bit edge was just detected
+///
+/// Returns `None` if the start bit turned out to be a glitch.
+fn receive_frame<RX, Timer, E>(
+    rx: &mut RX,
+    timer: &mut Timer,
+    config: SerialConfig,
+    oversampling: Oversampling,
+    inverted: bool,
+) -> Result<Option<u16>, crate::serial::Error<E>>
+where
+    RX: InputPin<Error = E>,
+    Timer: CountDown + Periodic,
+{
+    // Drop a tick left pending while polling for the start bit, so that
+    // the next one comes less than a tick after the edge
+    timer.wait().ok();
+
+    let mut decoder = FrameDecoder::new(config, oversampling);
+    loop {
+        block!(timer.wait()).ok();
+        let is_high = decoder.is_sampling() && rx.is_high().map_err(Error::Bus)? != inverted;
+        match decoder.tick(is_high) {
+            Decoded::Pending => {}
+            Decoded::FalseStart => return Ok(None),
+            Decoded::Frame(data) => return Ok(Some(data)),
+            Decoded::Error(error) => return Err(error),
+        }
+    }
+}
+
+/// Bit banging half-duplex serial communication device on a single wire
+///
+/// The pin is both driven and read back, e.g. an open-drain output with a
+/// pull-up, so that bits pulled low by another device while sending are
+/// reported as `Error::Collision`. Each bit is read back in its middle,
+/// leaving the pull-up half a bit to raise the line.
+pub struct HalfDuplexSerial<Pin, Timer>
+where
+    Pin: InputPin + OutputPin,
+    Timer: CountDown + Periodic,
+{
+    pin: Pin,
+    timer: Timer,
+    config: SerialConfig,
+    oversampling: Oversampling,
+    turnaround: u8,
+    suppress_echo: bool,
+    echo: Option<u16>,
+    overrun: bool,
+    transmitting: bool,
+    in_break: bool,
+}
+
+impl<Pin, Timer, E> HalfDuplexSerial<Pin, Timer>
+where
+    Pin: InputPin<Error = E> + OutputPin<Error = E>,
+    Timer: CountDown + Periodic,
+{
+    /// Create instance
+    pub fn new(pin: Pin, timer: Timer) -> Self {
+        HalfDuplexSerial {
+            pin,
+            timer,
+            config: SerialConfig::default(),
+            oversampling: Oversampling::default(),
+            turnaround: 0,
+            suppress_echo: false,
+            echo: None,
+            overrun: false,
+            transmitting: false,
+            in_break: false,
+        }
+    }
+
+    /// Set the frame format
+    pub fn set_config(&mut self, config: SerialConfig) {
+        self.config = config;
+    }
+
+    /// Set the number of timer ticks per bit
+    ///
+    /// The timer frequency must be adjusted accordingly.
+    pub fn set_oversampling(&mut self, oversampling: Oversampling) {
+        self.oversampling = oversampling;
+    }
+
+    /// Set the number of idle bit times waited before sending after the line
+    /// was last used by another device, giving it time to release the line
+    pub fn set_turnaround(&mut self, bit_times: u8) {
+        self.turnaround = bit_times;
+    }
+
+    /// Set whether sent words are dropped instead of being read back
+    ///
+    /// Without echo suppression, each sent word is returned by the next read,
+    /// like on a hardware single-wire UART. Only one such word is kept: sending
+    /// another one before reading it makes the next read return
+    /// `Error::Overrun`.
+    pub fn set_echo_suppression(&mut self, suppress_echo: bool) {
+        self.suppress_echo = suppress_echo;
+    }
+
+    /// Send one frame holding the lowest data bits of `word`, checking the
+    /// line for collisions
+    fn write_frame(&mut self, word: u16) -> Result<(), crate::serial::Error<E>> {
+        if !self.transmitting {
+            let ticks = u16::from(self.turnaround) * u16::from(self.oversampling.ticks_per_bit());
+            for _tick in 0..ticks {
+                block!(self.timer.wait()).ok();
+            }
+            self.transmitting = true;
+        }
+
+        let mut encoder = FrameEncoder::new(self.config, self.oversampling, word);
+        while !encoder.is_complete() {
+            if encoder.is_bit_start() {
+                self.set_pin(encoder.level())?;
+            }
+            if encoder.is_mid_bit() && self.pin.is_high().map_err(Error::Bus)? != encoder.level() {
+                // release the line to the other device
+                self.pin.set_high().map_err(Error::Bus)?;
+                return Err(Error::Collision);
+            }
+            block!(self.timer.wait()).ok();
+            encoder.tick();
+        }
+
+        if !self.suppress_echo {
+            self.overrun |= self.echo.replace(encoder.data).is_some();
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn set_pin(&mut self, high: bool) -> Result<(), crate::serial::Error<E>> {
+        if high {
+            self.pin.set_high().map_err(Error::Bus)
+        } else {
+            self.pin.set_low().map_err(Error::Bus)
+        }
+    }
+}
+
+impl<Pin, Timer, E, W> serial::Write<W> for HalfDuplexSerial<Pin, Timer>
+where
+    Pin: InputPin<Error = E> + OutputPin<Error = E>,
+    Timer: CountDown + Periodic,
+    W: Word,
+{
+    type Error = crate::serial::Error<E>;
+
+    fn write(&mut self, word: W) -> nb::Result<(), Self::Error> {
+        self.write_frame(word.into_data())?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl<Pin, Timer, E, W> serial::Read<W> for HalfDuplexSerial<Pin, Timer>
+where
+    Pin: InputPin<Error = E> + OutputPin<Error = E>,
+    Timer: CountDown + Periodic,
+    W: Word,
+{
+    type Error = crate::serial::Error<E>;
+
+    /// Read back a sent word if echo is not suppressed, or else read a frame
+    /// from the line
+    ///
+    /// As with [`Serial`], this must be polled at least once per timer tick
+    /// while waiting for data.
+    fn read(&mut self) -> nb::Result<W, Self::Error> {
+        if self.overrun {
+            self.overrun = false;
+            return Err(nb::Error::Other(Error::Overrun));
+        }
+        if let Some(data) = self.echo.take() {
+            return Ok(W::from_data(data));
+        }
+
+        let result = read_frame(
+            &mut self.pin,
+            &mut self.timer,
+            self.config,
+            self.oversampling,
+            false,
+            &mut self.in_break,
+        );
+        if !matches!(result, Err(nb::Error::WouldBlock)) {
+            self.transmitting = false;
         }
-        // wait for stop bit
-        self.wait_for_timer();
-        Ok(data_in)
+        result.map(W::from_data)
     }
 }
@@ -0,0 +1,330 @@
+//! Interrupt-driven serial communication
+//!
+//! Instead of blocking on the timer, these types are advanced from interrupt
+//! handlers and exchange words with the main loop through a lock-free
+//! [`Queue`](super::queue::Queue).
+//!
+//! The periodic timer runs at the baud rate multiplied by the
+//! [`Oversampling`] factor, with its interrupt handler calling
+//! [`Receiver::on_tick`] and [`Transmitter::on_tick`]. The falling edge of the
+//! start bit is found by `on_tick` itself, or earlier by calling
+//! [`Receiver::on_edge`] from a pin-change interrupt on RX. Either way, its
+//! first tick is the first one at or after the edge, so the edge is located
+//! within one tick like with the blocking [`Serial`](super::Serial).
+//!
+//! The main loop queues words for transmission through a [`Writer`], whose
+//! `flush` completes once the last stop bit has been sent.
+//!
+//! ```ignore
+//! static mut RX_QUEUE: Queue<u8, 64> = Queue::new();
+//! static mut TX_QUEUE: Queue<u8, 64> = Queue::new();
+//!
+//! // In initialization code
+//! let (producer, consumer) = unsafe { RX_QUEUE.split() };
+//! let mut receiver = Receiver::new(rx_pin, producer);
+//! receiver.set_oversampling(Oversampling::X16);
+//! let (producer, tx_consumer) = unsafe { TX_QUEUE.split() };
+//! let mut writer = Writer::new(producer);
+//! let mut transmitter = Transmitter::new(tx_pin, tx_consumer);
+//! transmitter.set_oversampling(Oversampling::X16);
+//!
+//! // In the RX pin-change interrupt handler
+//! receiver.on_edge().ok();
+//!
+//! // In the timer interrupt handler
+//! if let Err(error) = receiver.on_tick() {
+//!     // count or report the error
+//! }
+//! transmitter.on_tick().ok();
+//!
+//! // In the main loop
+//! writer.bwrite_all(b"hello\r\n").ok();
+//! while let Some(byte) = consumer.dequeue() {
+//!     // process byte
+//! }
+//! ```
+
+use super::queue::{Consumer, Producer};
+use super::{
+    Decoded, Error, FrameDecoder, FrameEncoder, Oversampling, RequestToSend, SerialConfig, Word,
+};
+use core::convert::Infallible;
+use embedded_hal::blocking::serial::write;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal::serial;
+
+/// Interrupt-driven serial receiver, pushing received words into a queue
+/// holding up to `N` of them
+///
+/// `RTS` is a flow control output, `()` when unused.
+pub struct Receiver<'a, RX, W, const N: usize, RTS = ()>
+where
+    RX: InputPin,
+    W: Word,
+{
+    rx: RX,
+    queue: Producer<'a, W, N>,
+    rts: RTS,
+    /// Number of free queue slots at or below which RTS is deasserted
+    rts_threshold: usize,
+    config: SerialConfig,
+    oversampling: Oversampling,
+    frame: Option<FrameDecoder>,
+    /// Whether the line is still low after a break
+    in_break: bool,
+}
+
+impl<'a, RX, W, E, const N: usize> Receiver<'a, RX, W, N>
+where
+    RX: InputPin<Error = E>,
+    W: Word,
+{
+    /// Create instance
+    pub fn new(rx: RX, queue: Producer<'a, W, N>) -> Self {
+        Receiver::with_rts(rx, queue, ())
+    }
+}
+
+impl<'a, RX, W, RTS, E, const N: usize> Receiver<'a, RX, W, N, RTS>
+where
+    RX: InputPin<Error = E>,
+    W: Word,
+    RTS: RequestToSend<E>,
+{
+    /// Create instance with an RTS output, deasserted while the queue is
+    /// nearly full
+    ///
+    /// RTS is updated from [`on_tick`](Self::on_tick).
+    pub fn with_rts(rx: RX, queue: Producer<'a, W, N>, rts: RTS) -> Self {
+        Receiver {
+            rx,
+            queue,
+            rts,
+            rts_threshold: 1,
+            config: SerialConfig::default(),
+            oversampling: Oversampling::default(),
+            frame: None,
+            in_break: false,
+        }
+    }
+
+    /// Set the number of free queue slots at or below which RTS is
+    /// deasserted, 1 by default so that a word already on its way still fits
+    pub fn set_rts_threshold(&mut self, free_slots: usize) {
+        self.rts_threshold = free_slots;
+    }
+
+    /// Set the frame format
+    pub fn set_config(&mut self, config: SerialConfig) {
+        self.config = config;
+    }
+
+    /// Set the number of timer ticks per bit
+    ///
+    /// The timer frequency must be adjusted accordingly.
+    pub fn set_oversampling(&mut self, oversampling: Oversampling) {
+        self.oversampling = oversampling;
+    }
+
+    /// Whether a frame is being received
+    pub fn is_busy(&self) -> bool {
+        self.frame.is_some()
+    }
+
+    /// Handle a falling edge on RX
+    ///
+    /// Call from the pin-change interrupt handler. Edges within a frame are
+    /// ignored.
+    pub fn on_edge(&mut self) -> Result<(), Error<E>> {
+        // a falling edge ends any break
+        self.in_break = false;
+        self.start_if_low()
+    }
+
+    /// Start receiving a frame if the line is low outside of a frame or break
+    fn start_if_low(&mut self) -> Result<(), Error<E>> {
+        if self.frame.is_some() {
+            return Ok(());
+        }
+        let is_low = self.rx.is_low().map_err(Error::Bus)?;
+        if self.in_break {
+            self.in_break = is_low;
+        } else if is_low {
+            self.frame = Some(FrameDecoder::new(self.config, self.oversampling));
+        }
+        Ok(())
+    }
+
+    /// Assert RTS unless the queue is nearly full
+    fn update_rts(&mut self) -> Result<(), Error<E>> {
+        let free_slots = N - self.queue.len();
+        self.rts
+            .set_ready(free_slots > self.rts_threshold)
+            .map_err(Error::Bus)
+    }
+
+    /// Advance reception by one timer tick
+    ///
+    /// Call from the periodic timer interrupt handler. Returns reception
+    /// errors, and `Error::Overrun` if a word was dropped because the queue was
+    /// full.
+    pub fn on_tick(&mut self) -> Result<(), Error<E>> {
+        let frame = match &mut self.frame {
+            Some(frame) => frame,
+            None => {
+                self.update_rts()?;
+                self.start_if_low()?;
+                // the tick that found the start bit low is its first tick
+                if let Some(frame) = &mut self.frame {
+                    frame.tick::<E>(false);
+                }
+                return Ok(());
+            }
+        };
+
+        let is_high = frame.is_sampling() && self.rx.is_high().map_err(Error::Bus)?;
+        let decoded = frame.tick(is_high);
+        if let Decoded::Pending = decoded {
+            return Ok(());
+        }
+
+        self.frame = None;
+        match decoded {
+            Decoded::Frame(data) => {
+                let result = self.queue.enqueue(W::from_data(data));
+                self.update_rts()?;
+                result.map_err(|_| Error::Overrun)
+            }
+            Decoded::Error(error) => {
+                self.in_break = matches!(error, Error::Break);
+                Err(error)
+            }
+            Decoded::Pending | Decoded::FalseStart => Ok(()),
+        }
+    }
+}
+
+/// Interrupt-driven serial transmitter, sending the words of a queue holding
+/// up to `N` of them
+///
+/// A word stays in the queue until its stop bits have been sent, so that an
+/// empty queue means the transmission is complete.
+pub struct Transmitter<'a, TX, W, const N: usize>
+where
+    TX: OutputPin,
+    W: Word,
+{
+    tx: TX,
+    queue: Consumer<'a, W, N>,
+    config: SerialConfig,
+    oversampling: Oversampling,
+    frame: Option<FrameEncoder>,
+}
+
+impl<'a, TX, W, E, const N: usize> Transmitter<'a, TX, W, N>
+where
+    TX: OutputPin<Error = E>,
+    W: Word,
+{
+    /// Create instance
+    pub fn new(tx: TX, queue: Consumer<'a, W, N>) -> Self {
+        Transmitter {
+            tx,
+            queue,
+            config: SerialConfig::default(),
+            oversampling: Oversampling::default(),
+            frame: None,
+        }
+    }
+
+    /// Set the frame format
+    pub fn set_config(&mut self, config: SerialConfig) {
+        self.config = config;
+    }
+
+    /// Set the number of timer ticks per bit
+    ///
+    /// The timer frequency must be adjusted accordingly.
+    pub fn set_oversampling(&mut self, oversampling: Oversampling) {
+        self.oversampling = oversampling;
+    }
+
+    /// Whether a frame is being sent
+    pub fn is_busy(&self) -> bool {
+        self.frame.is_some()
+    }
+
+    /// Advance transmission by one timer tick
+    ///
+    /// Call from the periodic timer interrupt handler.
+    pub fn on_tick(&mut self) -> Result<(), Error<E>> {
+        if let Some(frame) = &self.frame {
+            if frame.is_complete() {
+                self.frame = None;
+                self.queue.dequeue();
+            }
+        }
+        if self.frame.is_none() {
+            if let Some(word) = self.queue.peek() {
+                let frame = FrameEncoder::new(self.config, self.oversampling, word.into_data());
+                self.frame = Some(frame);
+            }
+        }
+
+        let frame = match &mut self.frame {
+            Some(frame) => frame,
+            None => return Ok(()),
+        };
+        if frame.is_bit_start() {
+            let result = if frame.level() {
+                self.tx.set_high()
+            } else {
+                self.tx.set_low()
+            };
+            result.map_err(Error::Bus)?;
+        }
+        frame.tick();
+        Ok(())
+    }
+}
+
+/// Main loop end of a [`Transmitter`], queueing words to send
+pub struct Writer<'a, W, const N: usize>
+where
+    W: Word,
+{
+    queue: Producer<'a, W, N>,
+}
+
+impl<'a, W, const N: usize> Writer<'a, W, N>
+where
+    W: Word,
+{
+    /// Create instance
+    pub fn new(queue: Producer<'a, W, N>) -> Self {
+        Writer { queue }
+    }
+}
+
+impl<W, const N: usize> serial::Write<W> for Writer<'_, W, N>
+where
+    W: Word,
+{
+    type Error = Infallible;
+
+    /// Queue a word, returning `WouldBlock` while the queue is full
+    fn write(&mut self, word: W) -> nb::Result<(), Self::Error> {
+        self.queue.enqueue(word).map_err(|_| nb::Error::WouldBlock)
+    }
+
+    /// Return `WouldBlock` until all queued words have been sent
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        if self.queue.is_empty() {
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+impl<W, const N: usize> write::Default<W> for Writer<'_, W, N> where W: Word {}
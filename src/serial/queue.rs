@@ -0,0 +1,192 @@
+//! Fixed-capacity lock-free single-producer single-consumer queue
+//!
+//! The queue is shared between an interrupt handler and the main loop without
+//! locking: [`Queue::split`] hands out one [`Producer`] and one [`Consumer`],
+//! each of which may live in a different execution context.
+//!
+//! ```
+//! use bitbang_hal::serial::queue::Queue;
+//!
+//! let mut queue: Queue<u8, 4> = Queue::new();
+//! let (mut producer, mut consumer) = queue.split();
+//!
+//! producer.enqueue(42).unwrap();
+//! assert_eq!(consumer.dequeue(), Some(42));
+//! assert_eq!(consumer.dequeue(), None);
+//! ```
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Queue holding up to `N` elements
+pub struct Queue<T, const N: usize> {
+    /// Number of elements dequeued so far, modulo `2 * N`
+    head: AtomicUsize,
+    /// Number of elements enqueued so far, modulo `2 * N`
+    ///
+    /// Counting modulo `2 * N` rather than `N` tells a full queue from an
+    /// empty one, and unlike a wrapping `usize` keeps `% N` consistent
+    /// across the wrap when `N` is not a power of two.
+    tail: AtomicUsize,
+    buffer: UnsafeCell<[MaybeUninit<T>; N]>,
+}
+
+// Producer and consumer only ever access disjoint slots
+unsafe impl<T: Send, const N: usize> Sync for Queue<T, N> {}
+
+impl<T: Copy, const N: usize> Queue<T, N> {
+    /// Create an empty queue
+    pub const fn new() -> Self {
+        Queue {
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            buffer: UnsafeCell::new([MaybeUninit::uninit(); N]),
+        }
+    }
+
+    /// Split the queue into its producer and consumer ends
+    pub fn split(&mut self) -> (Producer<'_, T, N>, Consumer<'_, T, N>) {
+        (Producer { queue: self }, Consumer { queue: self })
+    }
+
+    /// Maximum number of elements
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    fn len(&self) -> usize {
+        // `head` never passes `tail`, so loading it first keeps the result
+        // within `0..=N`
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+        Self::distance(head, tail)
+    }
+
+    /// Number of elements from counter `head` to counter `tail`
+    fn distance(head: usize, tail: usize) -> usize {
+        if tail >= head {
+            tail - head
+        } else {
+            tail + 2 * N - head
+        }
+    }
+
+    /// Counter following `counter`
+    fn next(counter: usize) -> usize {
+        if counter + 1 == 2 * N {
+            0
+        } else {
+            counter + 1
+        }
+    }
+
+    /// Buffer slot of the element at `counter`
+    fn slot(counter: usize) -> usize {
+        if counter >= N {
+            counter - N
+        } else {
+            counter
+        }
+    }
+}
+
+impl<T: Copy, const N: usize> Default for Queue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Producer end of a [`Queue`]
+pub struct Producer<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+}
+
+unsafe impl<T: Send, const N: usize> Send for Producer<'_, T, N> {}
+
+impl<T: Copy, const N: usize> Producer<'_, T, N> {
+    /// Add `item` at the back of the queue, or give it back if the queue is
+    /// full
+    pub fn enqueue(&mut self, item: T) -> Result<(), T> {
+        let tail = self.queue.tail.load(Ordering::Relaxed);
+        let head = self.queue.head.load(Ordering::Acquire);
+        if Queue::<T, N>::distance(head, tail) == N {
+            return Err(item);
+        }
+
+        // The consumer doesn't access this slot until `tail` is updated
+        unsafe {
+            (*self.queue.buffer.get())[Queue::<T, N>::slot(tail)] = MaybeUninit::new(item);
+        }
+        self.queue
+            .tail
+            .store(Queue::<T, N>::next(tail), Ordering::Release);
+        Ok(())
+    }
+
+    /// Number of elements in the queue
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Whether the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the queue is full
+    pub fn is_full(&self) -> bool {
+        self.len() == N
+    }
+
+    /// Maximum number of elements
+    pub fn capacity(&self) -> usize {
+        N
+    }
+}
+
+/// Consumer end of a [`Queue`]
+pub struct Consumer<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+}
+
+unsafe impl<T: Send, const N: usize> Send for Consumer<'_, T, N> {}
+
+impl<T: Copy, const N: usize> Consumer<'_, T, N> {
+    /// Remove the element at the front of the queue
+    pub fn dequeue(&mut self) -> Option<T> {
+        let item = self.peek()?;
+        let head = self.queue.head.load(Ordering::Relaxed);
+        self.queue
+            .head
+            .store(Queue::<T, N>::next(head), Ordering::Release);
+        Some(item)
+    }
+
+    /// Return the element at the front of the queue without removing it
+    pub fn peek(&self) -> Option<T> {
+        let head = self.queue.head.load(Ordering::Relaxed);
+        let tail = self.queue.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+
+        // The producer doesn't access this slot until `head` is updated
+        Some(unsafe { (*self.queue.buffer.get())[Queue::<T, N>::slot(head)].assume_init() })
+    }
+
+    /// Number of elements in the queue
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Whether the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Maximum number of elements
+    pub fn capacity(&self) -> usize {
+        N
+    }
+}
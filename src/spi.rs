@@ -12,11 +12,22 @@
 //!
 //! MSB-first and LSB-first bit orders are supported.
 //!
+//! For 3-wire devices sharing a single data line (SDIO) in both directions,
+//! [`HalfDuplexSPI`] takes a bidirectional [`IoPin`] instead of separate MOSI
+//! and MISO pins.
+//!
+//! For SPI NOR flash and similar devices, [`MultiIoSPI`] takes 2 or 4
+//! bidirectional data pins and supports Dual and Quad transfers, where each
+//! phase of a [`Transaction`] may use its own number of [`Lines`].
+//!
+//! [`SpiSlave`] implements the peripheral side of the bus, sampling SCK and
+//! CS driven by another master.
+//!
 
 pub use embedded_hal::spi::{MODE_0, MODE_1, MODE_2, MODE_3};
 
-use embedded_hal::digital::v2::{InputPin, OutputPin};
-use embedded_hal::spi::{FullDuplex, Mode, Polarity};
+use embedded_hal::digital::v2::{InputPin, IoPin, OutputPin, PinState};
+use embedded_hal::spi::{FullDuplex, Mode, Phase, Polarity};
 use embedded_hal::timer::{CountDown, Periodic};
 use nb::block;
 
@@ -27,6 +38,12 @@ pub enum Error<E> {
     Bus(E),
     /// Attempted read without input data
     NoData,
+    /// A byte was received before the previous one was read
+    Overrun,
+    /// Bidirectional pin was lost after a failed direction change
+    PinUnavailable,
+    /// Transfer phase uses more data lines than available
+    UnsupportedLines,
 }
 
 /// Transmission bit order
@@ -60,6 +77,7 @@ where
     sck: Sck,
     timer: Timer,
     read_val: Option<u8>,
+    overrun: bool,
     bit_order: BitOrder,
 }
 
@@ -79,6 +97,7 @@ where
             sck,
             timer,
             read_val: None,
+            overrun: false,
             bit_order: BitOrder::default(),
         };
 
@@ -124,30 +143,54 @@ where
         self.timer = f(timer);
     }
 
-    fn read_bit(&mut self) -> nb::Result<(), crate::spi::Error<E>> {
-        let is_miso_high = self.miso.is_high().map_err(Error::Bus)?;
-        let shifted_value = self.read_val.unwrap_or(0) << 1;
-        if is_miso_high {
-            self.read_val = Some(shifted_value | 1);
-        } else {
-            self.read_val = Some(shifted_value);
+    fn transfer_mode<const IDLE_HIGH: bool, const CAPTURE_ON_SECOND: bool>(
+        &mut self,
+        words: &mut [u8],
+    ) -> Result<(), crate::spi::Error<E>> {
+        for word in words.iter_mut() {
+            *word = self.exchange_byte::<IDLE_HIGH, CAPTURE_ON_SECOND>(*word)?;
         }
         Ok(())
     }
 
-    #[inline]
-    fn set_clk_high(&mut self) -> Result<(), crate::spi::Error<E>> {
-        self.sck.set_high().map_err(Error::Bus)
+    fn write_mode<const IDLE_HIGH: bool, const CAPTURE_ON_SECOND: bool>(
+        &mut self,
+        words: &[u8],
+    ) -> Result<(), crate::spi::Error<E>> {
+        for word in words {
+            self.exchange_byte::<IDLE_HIGH, CAPTURE_ON_SECOND>(*word)?;
+        }
+        Ok(())
     }
 
-    #[inline]
-    fn set_clk_low(&mut self) -> Result<(), crate::spi::Error<E>> {
-        self.sck.set_low().map_err(Error::Bus)
-    }
+    /// Shift one byte out and in, with the clock polarity and phase fixed at
+    /// compile time so the per-bit loop has no mode dispatch
+    #[inline(always)]
+    fn exchange_byte<const IDLE_HIGH: bool, const CAPTURE_ON_SECOND: bool>(
+        &mut self,
+        byte: u8,
+    ) -> Result<u8, crate::spi::Error<E>> {
+        let miso = &self.miso;
+        let mut byte_in = 0;
 
-    #[inline]
-    fn wait_for_timer(&mut self) {
-        block!(self.timer.wait()).ok();
+        for bit_offset in 0..8 {
+            let mask = bit_mask(bit_offset, &self.bit_order);
+            if byte & mask != 0 {
+                self.mosi.set_high().map_err(Error::Bus)?;
+            } else {
+                self.mosi.set_low().map_err(Error::Bus)?;
+            }
+
+            if clock_cycle::<IDLE_HIGH, CAPTURE_ON_SECOND, _, _, _, _>(
+                &mut self.sck,
+                &mut self.timer,
+                || miso.is_high().map_err(Error::Bus),
+            )? {
+                byte_in |= mask;
+            }
+        }
+
+        Ok(byte_in)
     }
 }
 
@@ -160,64 +203,41 @@ where
 {
     type Error = crate::spi::Error<E>;
 
+    /// Return the byte received by the last `send`
+    ///
+    /// Each received byte can be read once. If `send` was called again before
+    /// reading, the previous byte is lost and `Overrun` is returned once; the
+    /// most recent byte can then still be read.
     #[inline]
     fn read(&mut self) -> nb::Result<u8, Self::Error> {
-        match self.read_val {
+        if self.overrun {
+            self.overrun = false;
+            return Err(nb::Error::Other(crate::spi::Error::Overrun));
+        }
+
+        match self.read_val.take() {
             Some(val) => Ok(val),
             None => Err(nb::Error::Other(crate::spi::Error::NoData)),
         }
     }
 
     fn send(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
-        for bit_offset in 0..8 {
-            let out_bit = match self.bit_order {
-                BitOrder::MSBFirst => (byte >> (7 - bit_offset)) & 0b1,
-                BitOrder::LSBFirst => (byte >> bit_offset) & 0b1,
-            };
-
-            if out_bit == 1 {
-                self.mosi.set_high().map_err(Error::Bus)?;
-            } else {
-                self.mosi.set_low().map_err(Error::Bus)?;
-            }
+        let byte_in = match self.mode {
+            MODE_0 => self.exchange_byte::<false, false>(byte)?,
+            MODE_1 => self.exchange_byte::<false, true>(byte)?,
+            MODE_2 => self.exchange_byte::<true, false>(byte)?,
+            MODE_3 => self.exchange_byte::<true, true>(byte)?,
+        };
 
-            match self.mode {
-                MODE_0 => {
-                    self.wait_for_timer();
-                    self.set_clk_high()?;
-                    self.read_bit()?;
-                    self.wait_for_timer();
-                    self.set_clk_low()?;
-                }
-                MODE_1 => {
-                    self.set_clk_high()?;
-                    self.wait_for_timer();
-                    self.read_bit()?;
-                    self.set_clk_low()?;
-                    self.wait_for_timer();
-                }
-                MODE_2 => {
-                    self.wait_for_timer();
-                    self.set_clk_low()?;
-                    self.read_bit()?;
-                    self.wait_for_timer();
-                    self.set_clk_high()?;
-                }
-                MODE_3 => {
-                    self.set_clk_low()?;
-                    self.wait_for_timer();
-                    self.read_bit()?;
-                    self.set_clk_high()?;
-                    self.wait_for_timer();
-                }
-            }
+        if self.read_val.replace(byte_in).is_some() {
+            self.overrun = true;
         }
 
         Ok(())
     }
 }
 
-impl<Miso, Mosi, Sck, Timer, E> embedded_hal::blocking::spi::transfer::Default<u8>
+impl<Miso, Mosi, Sck, Timer, E> embedded_hal::blocking::spi::Transfer<u8>
     for SPI<Miso, Mosi, Sck, Timer>
 where
     Miso: InputPin<Error = E>,
@@ -225,9 +245,22 @@ where
     Sck: OutputPin<Error = E>,
     Timer: CountDown + Periodic,
 {
+    type Error = crate::spi::Error<E>;
+
+    /// Exchange `words` in place, choosing the clocking scheme once for the
+    /// whole buffer
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Self::Error> {
+        match self.mode {
+            MODE_0 => self.transfer_mode::<false, false>(words)?,
+            MODE_1 => self.transfer_mode::<false, true>(words)?,
+            MODE_2 => self.transfer_mode::<true, false>(words)?,
+            MODE_3 => self.transfer_mode::<true, true>(words)?,
+        }
+        Ok(words)
+    }
 }
 
-impl<Miso, Mosi, Sck, Timer, E> embedded_hal::blocking::spi::write::Default<u8>
+impl<Miso, Mosi, Sck, Timer, E> embedded_hal::blocking::spi::Write<u8>
     for SPI<Miso, Mosi, Sck, Timer>
 where
     Miso: InputPin<Error = E>,
@@ -235,4 +268,616 @@ where
     Sck: OutputPin<Error = E>,
     Timer: CountDown + Periodic,
 {
+    type Error = crate::spi::Error<E>;
+
+    /// Write `words`, choosing the clocking scheme once for the whole buffer
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        match self.mode {
+            MODE_0 => self.write_mode::<false, false>(words),
+            MODE_1 => self.write_mode::<false, true>(words),
+            MODE_2 => self.write_mode::<true, false>(words),
+            MODE_3 => self.write_mode::<true, true>(words),
+        }
+    }
+}
+
+/// A 3-wire Half-Duplex SPI implementation, takes a clock pin, a bidirectional
+/// data pin (SDIO), and a timer running at 2x the desired SPI frequency.
+///
+/// The data pin is switched to output mode while writing and to input mode
+/// while reading, so transactions consist of a write phase followed by an
+/// optional read phase.
+pub struct HalfDuplexSPI<SdioIn, SdioOut, Sck, Timer>
+where
+    SdioIn: InputPin + IoPin<SdioIn, SdioOut>,
+    SdioOut: OutputPin + IoPin<SdioIn, SdioOut>,
+    Sck: OutputPin,
+    Timer: CountDown + Periodic,
+{
+    mode: Mode,
+    sdio: Sdio<SdioIn, SdioOut>,
+    sck: Sck,
+    timer: Timer,
+    bit_order: BitOrder,
+}
+
+/// Bidirectional data pin in its current direction
+enum Sdio<SdioIn, SdioOut> {
+    Input(SdioIn),
+    Output(SdioOut),
+    /// The pin was consumed by a failed direction change
+    Unavailable,
+}
+
+impl<SdioIn, SdioOut, E> Sdio<SdioIn, SdioOut>
+where
+    SdioIn: InputPin<Error = E> + IoPin<SdioIn, SdioOut, Error = E>,
+    SdioOut: OutputPin<Error = E> + IoPin<SdioIn, SdioOut, Error = E>,
+{
+    /// Switch the pin to output mode if necessary and return it
+    fn output(&mut self) -> Result<&mut SdioOut, crate::spi::Error<E>> {
+        *self = match core::mem::replace(self, Sdio::Unavailable) {
+            Sdio::Input(pin) => {
+                Sdio::Output(pin.into_output_pin(PinState::Low).map_err(Error::Bus)?)
+            }
+            sdio => sdio,
+        };
+        match self {
+            Sdio::Output(pin) => Ok(pin),
+            _ => Err(Error::PinUnavailable),
+        }
+    }
+
+    /// Switch the pin to input mode if necessary and return it
+    fn input(&mut self) -> Result<&mut SdioIn, crate::spi::Error<E>> {
+        *self = match core::mem::replace(self, Sdio::Unavailable) {
+            Sdio::Output(pin) => Sdio::Input(pin.into_input_pin().map_err(Error::Bus)?),
+            sdio => sdio,
+        };
+        match self {
+            Sdio::Input(pin) => Ok(pin),
+            _ => Err(Error::PinUnavailable),
+        }
+    }
+}
+
+impl<SdioIn, SdioOut, Sck, Timer, E> HalfDuplexSPI<SdioIn, SdioOut, Sck, Timer>
+where
+    SdioIn: InputPin<Error = E> + IoPin<SdioIn, SdioOut, Error = E>,
+    SdioOut: OutputPin<Error = E> + IoPin<SdioIn, SdioOut, Error = E>,
+    Sck: OutputPin<Error = E>,
+    Timer: CountDown + Periodic,
+{
+    /// Create instance
+    pub fn new(mode: Mode, sdio: SdioIn, sck: Sck, timer: Timer) -> Self {
+        let mut spi = HalfDuplexSPI {
+            mode,
+            sdio: Sdio::Input(sdio),
+            sck,
+            timer,
+            bit_order: BitOrder::default(),
+        };
+
+        match mode.polarity {
+            Polarity::IdleLow => spi.sck.set_low(),
+            Polarity::IdleHigh => spi.sck.set_high(),
+        }
+        .unwrap_or(());
+
+        spi
+    }
+
+    /// Set transmission bit order
+    pub fn set_bit_order(&mut self, order: BitOrder) {
+        self.bit_order = order;
+    }
+
+    /// Write `output` to the device
+    pub fn write(&mut self, output: &[u8]) -> Result<(), crate::spi::Error<E>> {
+        for byte in output {
+            self.write_byte(*byte)?;
+        }
+        Ok(())
+    }
+
+    /// Read `input.len()` bytes from the device
+    pub fn read(&mut self, input: &mut [u8]) -> Result<(), crate::spi::Error<E>> {
+        for byte in input.iter_mut() {
+            *byte = self.read_byte()?;
+        }
+        Ok(())
+    }
+
+    /// Write `output` to the device, then turn the data line around and read
+    /// `input.len()` bytes back
+    pub fn write_read(
+        &mut self,
+        output: &[u8],
+        input: &mut [u8],
+    ) -> Result<(), crate::spi::Error<E>> {
+        self.write(output)?;
+        self.read(input)
+    }
+
+    fn write_byte(&mut self, byte: u8) -> Result<(), crate::spi::Error<E>> {
+        let sdio = self.sdio.output()?;
+
+        for bit_offset in 0..8 {
+            if byte & bit_mask(bit_offset, &self.bit_order) != 0 {
+                sdio.set_high().map_err(Error::Bus)?;
+            } else {
+                sdio.set_low().map_err(Error::Bus)?;
+            }
+
+            clock_cycle_in(self.mode, &mut self.sck, &mut self.timer, || Ok(()))?;
+        }
+
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> Result<u8, crate::spi::Error<E>> {
+        let sdio = self.sdio.input()?;
+
+        let mut byte = 0;
+        for bit_offset in 0..8 {
+            if clock_cycle_in(self.mode, &mut self.sck, &mut self.timer, || {
+                sdio.is_high().map_err(Error::Bus)
+            })? {
+                byte |= bit_mask(bit_offset, &self.bit_order);
+            }
+        }
+
+        Ok(byte)
+    }
+}
+
+impl<SdioIn, SdioOut, Sck, Timer, E> embedded_hal::blocking::spi::Write<u8>
+    for HalfDuplexSPI<SdioIn, SdioOut, Sck, Timer>
+where
+    SdioIn: InputPin<Error = E> + IoPin<SdioIn, SdioOut, Error = E>,
+    SdioOut: OutputPin<Error = E> + IoPin<SdioIn, SdioOut, Error = E>,
+    Sck: OutputPin<Error = E>,
+    Timer: CountDown + Periodic,
+{
+    type Error = crate::spi::Error<E>;
+
+    fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        HalfDuplexSPI::write(self, words)
+    }
+}
+
+/// Number of data lines used by a phase of a multi-IO SPI transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lines {
+    /// Standard SPI: IO0 as MOSI and IO1 as MISO
+    Single = 1,
+    /// Dual SPI: two bits per clock cycle on IO0-IO1
+    Dual = 2,
+    /// Quad SPI: four bits per clock cycle on IO0-IO3
+    Quad = 4,
+}
+
+impl Default for Lines {
+    /// Default number of lines: single
+    fn default() -> Self {
+        Lines::Single
+    }
+}
+
+/// Multi-IO SPI transaction header
+///
+/// Describes the instruction, address and dummy phases sent before the data
+/// phase, and how many lines each phase uses. For example, a Quad I/O Fast
+/// Read (1-4-4) from a NOR flash:
+///
+/// ```
+/// use bitbang_hal::spi::{Lines, Transaction};
+///
+/// let fast_read_quad_io = Transaction {
+///     instruction: Some(0xEB),
+///     address: Some(0x1000),
+///     address_lines: Lines::Quad,
+///     dummy_cycles: 6,
+///     data_lines: Lines::Quad,
+///     ..Transaction::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Transaction {
+    /// Instruction byte, if any
+    pub instruction: Option<u8>,
+    /// Lines used for the instruction phase
+    pub instruction_lines: Lines,
+    /// Address, if any
+    pub address: Option<u32>,
+    /// Address width in bytes (1 to 4), 3 if zero
+    pub address_bytes: u8,
+    /// Lines used for the address phase
+    pub address_lines: Lines,
+    /// Clock cycles between address and data phases, with the data lines
+    /// released
+    pub dummy_cycles: u8,
+    /// Lines used for the data phase
+    pub data_lines: Lines,
+}
+
+/// A multi-IO (Dual/Quad) SPI implementation, takes a clock pin, 2 or 4
+/// bidirectional data pins (IO0-IO3) and a timer running at 2x the desired SPI
+/// frequency.
+///
+/// All data pins must have the same type, e.g. the type-erased pins provided
+/// by most HALs. Bytes are always transferred MSB first; with multiple lines,
+/// the highest-numbered line carries the most significant bit of each cycle.
+pub struct MultiIoSPI<IoIn, IoOut, Sck, Timer, const N: usize>
+where
+    IoIn: InputPin + IoPin<IoIn, IoOut>,
+    IoOut: OutputPin + IoPin<IoIn, IoOut>,
+    Sck: OutputPin,
+    Timer: CountDown + Periodic,
+{
+    mode: Mode,
+    io: [Sdio<IoIn, IoOut>; N],
+    sck: Sck,
+    timer: Timer,
+}
+
+impl<IoIn, IoOut, Sck, Timer, E, const N: usize> MultiIoSPI<IoIn, IoOut, Sck, Timer, N>
+where
+    IoIn: InputPin<Error = E> + IoPin<IoIn, IoOut, Error = E>,
+    IoOut: OutputPin<Error = E> + IoPin<IoIn, IoOut, Error = E>,
+    Sck: OutputPin<Error = E>,
+    Timer: CountDown + Periodic,
+{
+    /// Create instance
+    ///
+    /// `io` holds the data pins in order IO0 to IO3. Only 2 (Dual) or 4
+    /// (Quad) pins are useful.
+    pub fn new(mode: Mode, io: [IoIn; N], sck: Sck, timer: Timer) -> Self {
+        let mut spi = MultiIoSPI {
+            mode,
+            io: io.map(Sdio::Input),
+            sck,
+            timer,
+        };
+
+        match mode.polarity {
+            Polarity::IdleLow => spi.sck.set_low(),
+            Polarity::IdleHigh => spi.sck.set_high(),
+        }
+        .unwrap_or(());
+
+        spi
+    }
+
+    /// Send the transaction header, then read `input.len()` bytes in the data
+    /// phase
+    pub fn read(
+        &mut self,
+        transaction: &Transaction,
+        input: &mut [u8],
+    ) -> Result<(), crate::spi::Error<E>> {
+        self.header(transaction)?;
+        self.shift_in(input, transaction.data_lines)
+    }
+
+    /// Send the transaction header, then write `output` in the data phase
+    pub fn write(
+        &mut self,
+        transaction: &Transaction,
+        output: &[u8],
+    ) -> Result<(), crate::spi::Error<E>> {
+        self.header(transaction)?;
+        self.shift_out(output, transaction.data_lines)
+    }
+
+    fn header(&mut self, transaction: &Transaction) -> Result<(), crate::spi::Error<E>> {
+        if let Some(instruction) = transaction.instruction {
+            self.shift_out(&[instruction], transaction.instruction_lines)?;
+        }
+
+        if let Some(address) = transaction.address {
+            let width = match transaction.address_bytes {
+                0 => 3,
+                width => usize::from(width.min(4)),
+            };
+            let address = address.to_be_bytes();
+            self.shift_out(&address[4 - width..], transaction.address_lines)?;
+        }
+
+        for io in self.io.iter_mut() {
+            io.input()?;
+        }
+        for _ in 0..transaction.dummy_cycles {
+            clock_cycle_in(self.mode, &mut self.sck, &mut self.timer, || Ok(()))?;
+        }
+
+        Ok(())
+    }
+
+    fn shift_out(&mut self, output: &[u8], lines: Lines) -> Result<(), crate::spi::Error<E>> {
+        let width = self.width(lines)?;
+        for io in self.io[width..].iter_mut() {
+            io.input()?;
+        }
+
+        for byte in output {
+            for cycle in 0..8 / width {
+                let bits = byte >> (8 - (cycle + 1) * width);
+                for (line, io) in self.io[..width].iter_mut().enumerate() {
+                    if (bits >> line) & 0b1 == 1 {
+                        io.output()?.set_high().map_err(Error::Bus)?;
+                    } else {
+                        io.output()?.set_low().map_err(Error::Bus)?;
+                    }
+                }
+
+                clock_cycle_in(self.mode, &mut self.sck, &mut self.timer, || Ok(()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn shift_in(&mut self, input: &mut [u8], lines: Lines) -> Result<(), crate::spi::Error<E>> {
+        let width = self.width(lines)?;
+        // In single mode the device answers on IO1 (MISO)
+        let first = if lines == Lines::Single { 1 } else { 0 };
+
+        for byte in input.iter_mut() {
+            *byte = 0;
+            for _ in 0..8 / width {
+                let io = &mut self.io[first..first + width];
+                let bits = clock_cycle_in(self.mode, &mut self.sck, &mut self.timer, || {
+                    let mut bits = 0;
+                    for (line, io) in io.iter_mut().enumerate() {
+                        if io.input()?.is_high().map_err(Error::Bus)? {
+                            bits |= 1 << line;
+                        }
+                    }
+                    Ok(bits)
+                })?;
+                *byte = (*byte << width) | bits;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Number of lines used by `lines`, if this instance has enough pins
+    fn width(&self, lines: Lines) -> Result<usize, crate::spi::Error<E>> {
+        let width = lines as usize;
+        if width > N || (lines == Lines::Single && N < 2) {
+            Err(Error::UnsupportedLines)
+        } else {
+            Ok(width)
+        }
+    }
+}
+
+/// An SPI slave (peripheral) implementation, takes SCK, CS and MOSI input pins
+/// and a MISO output pin, all driven by an external master.
+///
+/// The bus is sampled by calling [`poll`](SpiSlave::poll), either from a loop
+/// or from a pin-change interrupt on SCK and CS. It must be called at least
+/// once per SCK edge.
+///
+/// Data to answer with is preloaded into a buffer of `N` bytes, and up to `N`
+/// bytes received from the master are kept until the next transaction.
+pub struct SpiSlave<Sck, Cs, Mosi, Miso, const N: usize>
+where
+    Sck: InputPin,
+    Cs: InputPin,
+    Mosi: InputPin,
+    Miso: OutputPin,
+{
+    mode: Mode,
+    sck: Sck,
+    cs: Cs,
+    mosi: Mosi,
+    miso: Miso,
+    bit_order: BitOrder,
+    selected: bool,
+    sck_high: bool,
+    bit_offset: u8,
+    byte_in: u8,
+    index: usize,
+    response: [u8; N],
+    response_len: usize,
+    received: [u8; N],
+}
+
+impl<Sck, Cs, Mosi, Miso, E, const N: usize> SpiSlave<Sck, Cs, Mosi, Miso, N>
+where
+    Sck: InputPin<Error = E>,
+    Cs: InputPin<Error = E>,
+    Mosi: InputPin<Error = E>,
+    Miso: OutputPin<Error = E>,
+{
+    /// Create instance
+    pub fn new(mode: Mode, sck: Sck, cs: Cs, mosi: Mosi, miso: Miso) -> Self {
+        SpiSlave {
+            mode,
+            sck,
+            cs,
+            mosi,
+            miso,
+            bit_order: BitOrder::default(),
+            selected: false,
+            sck_high: false,
+            bit_offset: 0,
+            byte_in: 0,
+            index: 0,
+            response: [0; N],
+            response_len: 0,
+            received: [0; N],
+        }
+    }
+
+    /// Set transmission bit order
+    pub fn set_bit_order(&mut self, order: BitOrder) {
+        self.bit_order = order;
+    }
+
+    /// Set the data sent to the master in the following transactions
+    ///
+    /// At most `N` bytes are kept. Once they are exhausted, 0xFF is sent.
+    pub fn preload(&mut self, response: &[u8]) {
+        let len = response.len().min(N);
+        self.response[..len].copy_from_slice(&response[..len]);
+        self.response_len = len;
+    }
+
+    /// Bytes received from the master in the last transaction
+    pub fn received(&self) -> &[u8] {
+        &self.received[..self.index.min(N)]
+    }
+
+    /// Sample the bus and shift data for any CS or SCK edge since the last call
+    ///
+    /// Returns the number of bytes received once the master ends the
+    /// transaction by releasing CS. Bytes past the first `N` are dropped.
+    pub fn poll(&mut self) -> Result<Option<usize>, crate::spi::Error<E>> {
+        let selected = self.cs.is_low().map_err(Error::Bus)?;
+        if selected != self.selected {
+            self.selected = selected;
+            if !selected {
+                return Ok(Some(self.index.min(N)));
+            }
+
+            self.sck_high = self.sck.is_high().map_err(Error::Bus)?;
+            self.bit_offset = 0;
+            self.byte_in = 0;
+            self.index = 0;
+            if self.mode.phase == Phase::CaptureOnFirstTransition {
+                self.output_bit()?;
+            }
+            return Ok(None);
+        }
+
+        if !selected {
+            return Ok(None);
+        }
+
+        let sck_high = self.sck.is_high().map_err(Error::Bus)?;
+        if sck_high == self.sck_high {
+            return Ok(None);
+        }
+        self.sck_high = sck_high;
+
+        let leading_edge = sck_high == (self.mode.polarity == Polarity::IdleLow);
+        let capture_edge = leading_edge == (self.mode.phase == Phase::CaptureOnFirstTransition);
+        if capture_edge {
+            self.input_bit()?;
+        } else {
+            self.output_bit()?;
+        }
+
+        Ok(None)
+    }
+
+    fn output_bit(&mut self) -> Result<(), crate::spi::Error<E>> {
+        let byte = if self.index < self.response_len {
+            self.response[self.index]
+        } else {
+            0xFF
+        };
+
+        if byte & bit_mask(self.bit_offset, &self.bit_order) != 0 {
+            self.miso.set_high().map_err(Error::Bus)
+        } else {
+            self.miso.set_low().map_err(Error::Bus)
+        }
+    }
+
+    fn input_bit(&mut self) -> Result<(), crate::spi::Error<E>> {
+        if self.mosi.is_high().map_err(Error::Bus)? {
+            self.byte_in |= bit_mask(self.bit_offset, &self.bit_order);
+        }
+
+        self.bit_offset += 1;
+        if self.bit_offset == 8 {
+            if self.index < N {
+                self.received[self.index] = self.byte_in;
+            }
+            self.index += 1;
+            self.bit_offset = 0;
+            self.byte_in = 0;
+        }
+
+        Ok(())
+    }
+}
+
+/// Mask of the bit transferred at `bit_offset` for the given bit order
+#[inline]
+fn bit_mask(bit_offset: u8, bit_order: &BitOrder) -> u8 {
+    match bit_order {
+        BitOrder::MSBFirst => 1 << (7 - bit_offset),
+        BitOrder::LSBFirst => 1 << bit_offset,
+    }
+}
+
+/// Generate one SCK cycle for `mode`, calling `sample` to read the input
+/// line(s) at the sampling edge.
+///
+/// The output line(s) must already hold the bit(s) to transmit.
+fn clock_cycle_in<Sck, Timer, E, T, F>(
+    mode: Mode,
+    sck: &mut Sck,
+    timer: &mut Timer,
+    sample: F,
+) -> Result<T, crate::spi::Error<E>>
+where
+    Sck: OutputPin<Error = E>,
+    Timer: CountDown + Periodic,
+    F: FnOnce() -> Result<T, crate::spi::Error<E>>,
+{
+    match mode {
+        MODE_0 => clock_cycle::<false, false, _, _, _, _>(sck, timer, sample),
+        MODE_1 => clock_cycle::<false, true, _, _, _, _>(sck, timer, sample),
+        MODE_2 => clock_cycle::<true, false, _, _, _, _>(sck, timer, sample),
+        MODE_3 => clock_cycle::<true, true, _, _, _, _>(sck, timer, sample),
+    }
+}
+
+/// Generate one SCK cycle, with the clock polarity and phase fixed at compile
+/// time, calling `sample` to read the input line(s) at the sampling edge.
+///
+/// The output line(s) must already hold the bit(s) to transmit.
+#[inline(always)]
+fn clock_cycle<const IDLE_HIGH: bool, const CAPTURE_ON_SECOND: bool, Sck, Timer, E, T>(
+    sck: &mut Sck,
+    timer: &mut Timer,
+    sample: impl FnOnce() -> Result<T, crate::spi::Error<E>>,
+) -> Result<T, crate::spi::Error<E>>
+where
+    Sck: OutputPin<Error = E>,
+    Timer: CountDown + Periodic,
+{
+    if !CAPTURE_ON_SECOND {
+        block!(timer.wait()).ok();
+    }
+    if IDLE_HIGH {
+        sck.set_low().map_err(Error::Bus)?;
+    } else {
+        sck.set_high().map_err(Error::Bus)?;
+    }
+    if CAPTURE_ON_SECOND {
+        block!(timer.wait()).ok();
+    }
+
+    let value = sample()?;
+
+    if !CAPTURE_ON_SECOND {
+        block!(timer.wait()).ok();
+    }
+    if IDLE_HIGH {
+        sck.set_high().map_err(Error::Bus)?;
+    } else {
+        sck.set_low().map_err(Error::Bus)?;
+    }
+    if CAPTURE_ON_SECOND {
+        block!(timer.wait()).ok();
+    }
+    Ok(value)
 }
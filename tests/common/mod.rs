@@ -0,0 +1,468 @@
+//! Simulated pins and timers for host-side tests
+
+#![allow(dead_code)]
+
+use bitbang_hal::serial::{Oversampling, Serial};
+use core::convert::Infallible;
+use embedded_hal::digital::v2::{InputPin, IoPin, OutputPin, PinState};
+use embedded_hal::timer::{CountDown, Periodic};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+/// Simulated time units per serial bit
+pub const BIT: u64 = 4800;
+
+/// A single wire shared by any number of pins
+#[derive(Clone)]
+pub struct Wire(Rc<Cell<bool>>);
+
+impl Wire {
+    pub fn new(level: bool) -> Self {
+        Wire(Rc::new(Cell::new(level)))
+    }
+
+    pub fn pin(&self) -> SimPin {
+        SimPin(self.clone())
+    }
+
+    pub fn is_high(&self) -> bool {
+        self.0.get()
+    }
+}
+
+/// Pin connected to a `Wire`, usable as input and output
+pub struct SimPin(Wire);
+
+impl InputPin for SimPin {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok((self.0).0.get())
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!(self.0).0.get())
+    }
+}
+
+impl OutputPin for SimPin {
+    type Error = Infallible;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        (self.0).0.set(true);
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        (self.0).0.set(false);
+        Ok(())
+    }
+}
+
+/// Periodic timer that expires immediately
+pub struct NoDelay;
+
+impl CountDown for NoDelay {
+    type Time = ();
+
+    fn start<T>(&mut self, _count: T)
+    where
+        T: Into<Self::Time>,
+    {
+    }
+
+    fn wait(&mut self) -> nb::Result<(), void::Void> {
+        Ok(())
+    }
+}
+
+impl Periodic for NoDelay {}
+
+/// Periodic timer that expires immediately after calling its hook, e.g. to
+/// poll a simulated device between the clock edges of a master
+pub struct HookTimer<F: FnMut()>(pub F);
+
+impl<F: FnMut()> CountDown for HookTimer<F> {
+    type Time = ();
+
+    fn start<T>(&mut self, _count: T)
+    where
+        T: Into<Self::Time>,
+    {
+    }
+
+    fn wait(&mut self) -> nb::Result<(), void::Void> {
+        (self.0)();
+        Ok(())
+    }
+}
+
+impl<F: FnMut()> Periodic for HookTimer<F> {}
+
+/// Error of the pins of an `IoLine`, returned by failed direction changes
+#[derive(Debug, PartialEq)]
+pub struct PinError;
+
+/// Line shared by a bidirectional pin of a master and by a device, which
+/// only sets the level while the master pin is an input
+#[derive(Clone)]
+pub struct IoLine {
+    master: Rc<Cell<Option<bool>>>,
+    device: Rc<Cell<bool>>,
+    fail: Rc<Cell<bool>>,
+}
+
+/// Line pulled high, with the master pin in input mode
+impl Default for IoLine {
+    fn default() -> Self {
+        IoLine {
+            master: Rc::new(Cell::new(None)),
+            device: Rc::new(Cell::new(true)),
+            fail: Rc::new(Cell::new(false)),
+        }
+    }
+}
+
+impl IoLine {
+    /// Master end of the line, initially in input mode
+    pub fn master(&self) -> IoInput {
+        IoInput(self.clone())
+    }
+
+    /// Device end of the line
+    pub fn device(&self) -> DevicePin {
+        DevicePin(self.clone())
+    }
+
+    pub fn level(&self) -> bool {
+        self.master.get().unwrap_or_else(|| self.device.get())
+    }
+
+    /// Whether the master pin is in output mode
+    pub fn is_master_driven(&self) -> bool {
+        self.master.get().is_some()
+    }
+
+    /// Make the following direction changes of the master pin fail
+    pub fn fail_direction_changes(&self) {
+        self.fail.set(true);
+    }
+
+    fn check_direction_change(&self) -> Result<(), PinError> {
+        if self.fail.get() {
+            Err(PinError)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Master end of an `IoLine` in input mode
+pub struct IoInput(IoLine);
+
+/// Master end of an `IoLine` in output mode
+pub struct IoOutput(IoLine);
+
+impl InputPin for IoInput {
+    type Error = PinError;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.0.level())
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.0.level())
+    }
+}
+
+impl OutputPin for IoOutput {
+    type Error = PinError;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.master.set(Some(true));
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.master.set(Some(false));
+        Ok(())
+    }
+}
+
+impl IoPin<IoInput, IoOutput> for IoInput {
+    type Error = PinError;
+
+    fn into_input_pin(self) -> Result<IoInput, Self::Error> {
+        Ok(self)
+    }
+
+    fn into_output_pin(self, state: PinState) -> Result<IoOutput, Self::Error> {
+        self.0.check_direction_change()?;
+        self.0.master.set(Some(state == PinState::High));
+        Ok(IoOutput(self.0))
+    }
+}
+
+impl IoPin<IoInput, IoOutput> for IoOutput {
+    type Error = PinError;
+
+    fn into_input_pin(self) -> Result<IoInput, Self::Error> {
+        self.0.check_direction_change()?;
+        self.0.master.set(None);
+        Ok(IoInput(self.0))
+    }
+
+    fn into_output_pin(mut self, state: PinState) -> Result<IoOutput, Self::Error> {
+        self.set_state(state)?;
+        Ok(self)
+    }
+}
+
+/// Device end of an `IoLine`, reading the line and setting the level the
+/// device drives
+///
+/// Also usable by a master as an output the device reads, e.g. a clock.
+pub struct DevicePin(IoLine);
+
+impl InputPin for DevicePin {
+    type Error = PinError;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.0.level())
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.0.level())
+    }
+}
+
+impl OutputPin for DevicePin {
+    type Error = PinError;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.device.set(true);
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.device.set(false);
+        Ok(())
+    }
+}
+
+/// Simulated time, shared by timers and serial lines
+#[derive(Clone, Default)]
+pub struct Clock(Rc<Cell<u64>>);
+
+impl Clock {
+    pub fn now(&self) -> u64 {
+        self.0.get()
+    }
+
+    pub fn set(&self, now: u64) {
+        self.0.set(now)
+    }
+
+    /// Free-running periodic timer whose first tick is `offset` after now
+    pub fn timer(&self, period: u64, offset: u64) -> SimTimer {
+        SimTimer {
+            clock: self.clone(),
+            period,
+            next: self.now() + offset,
+        }
+    }
+}
+
+/// Periodic timer driven by a `Clock`
+///
+/// Each poll that would block takes one unit of simulated time. Ticks missed
+/// while the caller was busy are coalesced, like a hardware update flag.
+/// Restarting it sets its period.
+pub struct SimTimer {
+    clock: Clock,
+    period: u64,
+    next: u64,
+}
+
+impl CountDown for SimTimer {
+    type Time = u64;
+
+    fn start<T>(&mut self, period: T)
+    where
+        T: Into<Self::Time>,
+    {
+        self.period = period.into();
+        self.next = self.clock.now() + self.period;
+    }
+
+    fn wait(&mut self) -> nb::Result<(), void::Void> {
+        let now = self.clock.now();
+        if now < self.next {
+            self.clock.set(now + 1);
+            return Err(nb::Error::WouldBlock);
+        }
+        while self.next <= now {
+            self.next += self.period;
+        }
+        Ok(())
+    }
+}
+
+impl Periodic for SimTimer {}
+
+/// Serial line recording every level change with its timestamp, idle high
+#[derive(Clone)]
+pub struct SerialLine {
+    clock: Clock,
+    changes: Rc<RefCell<Vec<(u64, bool)>>>,
+}
+
+impl SerialLine {
+    pub fn new(clock: &Clock) -> Self {
+        SerialLine {
+            clock: clock.clone(),
+            changes: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    /// Transmitter end of the line
+    pub fn tx(&self) -> LineTx {
+        LineTx(self.clone())
+    }
+
+    /// Receiver end of the line, reading the recorded levels back
+    pub fn rx(&self) -> LineRx {
+        LineRx(self.clone())
+    }
+
+    /// Time of the first level change
+    pub fn first_change(&self) -> Option<u64> {
+        self.changes.borrow().first().map(|(time, _)| *time)
+    }
+
+    /// Every level change with its timestamp
+    pub fn changes(&self) -> Vec<(u64, bool)> {
+        self.changes.borrow().clone()
+    }
+
+    fn level_at(&self, time: u64) -> bool {
+        let changes = self.changes.borrow();
+        match changes.partition_point(|(changed, _)| *changed <= time) {
+            0 => true,
+            i => changes[i - 1].1,
+        }
+    }
+}
+
+pub struct LineTx(SerialLine);
+
+impl OutputPin for LineTx {
+    type Error = Infallible;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        let now = self.0.clock.now();
+        self.0.changes.borrow_mut().push((now, true));
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        let now = self.0.clock.now();
+        self.0.changes.borrow_mut().push((now, false));
+        Ok(())
+    }
+}
+
+/// Each read of the receiver end takes one unit of simulated time, so polling
+/// for a start bit makes progress
+pub struct LineRx(SerialLine);
+
+impl InputPin for LineRx {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        let now = self.0.clock.now();
+        self.0.clock.set(now + 1);
+        Ok(self.0.level_at(now))
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.is_high()?)
+    }
+}
+
+/// Open-drain pin on a `SerialLine`: driving it low pulls the line low, and
+/// reading it returns the level of the line, low if either end pulls it low
+///
+/// Levels driven through this pin are recorded separately, on `driven`.
+/// Once released, the line only reads high after `rise_time`, like a pull-up
+/// charging the line capacitance.
+pub struct LineIo {
+    line: SerialLine,
+    pub driven: SerialLine,
+    pub rise_time: u64,
+}
+
+impl LineIo {
+    pub fn new(line: &SerialLine) -> Self {
+        LineIo {
+            line: line.clone(),
+            driven: SerialLine::new(&line.clock),
+            rise_time: 0,
+        }
+    }
+}
+
+impl OutputPin for LineIo {
+    type Error = Infallible;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.driven.tx().set_high()
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.driven.tx().set_low()
+    }
+}
+
+/// Each read takes one unit of simulated time, like `LineRx`
+impl InputPin for LineIo {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        let now = self.line.clock.now();
+        self.line.clock.set(now + 1);
+        let released = now.saturating_sub(self.rise_time);
+        Ok(self.line.level_at(now) && self.driven.level_at(now) && self.driven.level_at(released))
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.is_high()?)
+    }
+}
+
+/// Serial device sending on `line` with the default 3x oversampling, its
+/// timer starting now
+pub fn serial_sender(clock: &Clock, line: &SerialLine) -> Serial<LineTx, SimPin, SimTimer> {
+    Serial::new(
+        line.tx(),
+        Wire::new(true).pin(),
+        clock.timer(BIT / 3, BIT / 3),
+    )
+}
+
+/// Serial device with 16x oversampling, its timer starting now
+pub fn oversampled_serial<TX, RX>(tx: TX, rx: RX, clock: &Clock) -> Serial<TX, RX, SimTimer>
+where
+    TX: OutputPin<Error = Infallible>,
+    RX: InputPin<Error = Infallible>,
+{
+    let mut serial = Serial::new(tx, rx, clock.timer(BIT / 16, BIT / 32));
+    serial.set_oversampling(Oversampling::X16);
+    serial
+}
+
+/// Serial device receiving `line` with 16x oversampling, its timer starting
+/// now
+pub fn serial_receiver(clock: &Clock, line: &SerialLine) -> Serial<SimPin, LineRx, SimTimer> {
+    oversampled_serial(Wire::new(true).pin(), line.rx(), clock)
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use bitbang_hal::dmx::{
+    Dmx, Error, MIN_BREAK_BITS, MIN_MARK_AFTER_BREAK_BITS, NULL_START_CODE, UNIVERSE,
+};
+use bitbang_hal::serial::{DriverEnable, Serial};
+use common::{
+    serial_receiver, serial_sender, Clock, LineRx, SerialLine, SimPin, SimTimer, Wire, BIT,
+};
+use embedded_hal::digital::v2::PinState;
+use nb::block;
+
+/// Receiver with 16x oversampling, reading `line` from time 0
+fn receiver(clock: &Clock, line: &SerialLine) -> Dmx<SimPin, LineRx, SimTimer> {
+    clock.set(0);
+    Dmx::new(serial_receiver(clock, line))
+}
+
+#[test]
+fn full_universe_round_trip() {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let mut sender = Dmx::new(serial_sender(&clock, &line));
+    let universe: Vec<u8> = (0..UNIVERSE).map(|slot| (slot * 7) as u8).collect();
+    sender.send(NULL_START_CODE, &universe).unwrap();
+
+    // break, then mark after break until the start bit of the start code
+    assert_eq!(
+        line.changes()[..3],
+        [(0, false), (44 * BIT, true), (48 * BIT, false)]
+    );
+
+    let mut dmx = receiver(&clock, &line);
+    let mut slots = [0; UNIVERSE];
+    let count = block!(dmx.receive(&mut slots)).unwrap();
+    assert_eq!(count, UNIVERSE);
+    assert_eq!(slots[..], universe[..]);
+}
+
+#[test]
+fn short_packets_end_on_next_break() {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let mut sender = Dmx::new(serial_sender(&clock, &line));
+    sender.set_timing(23, 3);
+    sender.send(NULL_START_CODE, &[1, 2, 3]).unwrap();
+    sender.send(NULL_START_CODE, &[4, 5]).unwrap();
+
+    let mut dmx = receiver(&clock, &line);
+    let mut slots = [0; UNIVERSE];
+    assert_eq!(block!(dmx.receive(&mut slots)).unwrap(), 3);
+    assert_eq!(slots[..3], [1, 2, 3]);
+    assert_eq!(block!(dmx.receive(&mut slots)).unwrap(), 2);
+    assert_eq!(slots[..2], [4, 5]);
+}
+
+#[test]
+fn timing_is_raised_to_minimums() {
+    let clock = Clock::default();
+    let short = SerialLine::new(&clock);
+    let mut sender = Dmx::new(serial_sender(&clock, &short));
+    sender.set_timing(1, 0);
+    sender.send(NULL_START_CODE, &[1]).unwrap();
+
+    clock.set(0);
+    let minimum = SerialLine::new(&clock);
+    let mut sender = Dmx::new(serial_sender(&clock, &minimum));
+    sender.set_timing(MIN_BREAK_BITS, MIN_MARK_AFTER_BREAK_BITS);
+    sender.send(NULL_START_CODE, &[1]).unwrap();
+
+    assert_eq!(short.changes(), minimum.changes());
+}
+
+#[test]
+fn other_start_code_is_reported() {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let mut sender = Dmx::new(serial_sender(&clock, &line));
+    sender.send(0xCC, &[1, 2, 3]).unwrap();
+
+    let mut dmx = receiver(&clock, &line);
+    let mut slots = [0; UNIVERSE];
+    let result = block!(dmx.receive(&mut slots));
+    assert!(matches!(result, Err(Error::StartCode(0xCC))));
+}
+
+#[test]
+fn driver_is_enabled_for_the_whole_packet() {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let de_line = SerialLine::new(&clock);
+    let serial = Serial::with_driver_enable(
+        line.tx(),
+        Wire::new(true).pin(),
+        clock.timer(BIT / 3, BIT / 3),
+        DriverEnable::new(de_line.tx(), PinState::High),
+    )
+    .unwrap();
+    let mut sender = Dmx::new(serial);
+    sender.send(NULL_START_CODE, &[1, 2, 3]).unwrap();
+
+    // break, mark after break and four 11-bit slots
+    assert_eq!(
+        de_line.changes(),
+        [(0, false), (0, true), (92 * BIT, false)]
+    );
+    assert_eq!(line.changes()[0], (0, false));
+
+    let mut dmx = receiver(&clock, &line);
+    let mut slots = [0; UNIVERSE];
+    assert_eq!(block!(dmx.receive(&mut slots)).unwrap(), 3);
+    assert_eq!(slots[..3], [1, 2, 3]);
+}
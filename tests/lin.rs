@@ -0,0 +1,131 @@
+mod common;
+
+use bitbang_hal::lin::{
+    checksum, protected_id, Checksum, Error, Handler, Master, Response, Slave, MAX_DATA,
+};
+use common::{
+    oversampled_serial, serial_receiver, Clock, LineTx, SerialLine, SimPin, SimTimer, Wire,
+};
+use nb::block;
+
+/// Slave handler publishing or receiving a single frame
+#[derive(Default)]
+struct TestHandler {
+    publish: Option<(u8, Vec<u8>)>,
+    subscribe: Option<(u8, usize)>,
+    received: Option<(u8, Vec<u8>)>,
+}
+
+impl Handler for TestHandler {
+    fn on_header(&mut self, id: u8, data: &mut [u8; MAX_DATA]) -> Response {
+        match (&self.publish, self.subscribe) {
+            (Some((publish_id, bytes)), _) if *publish_id == id => {
+                data[..bytes.len()].copy_from_slice(bytes);
+                Response::Publish(bytes.len())
+            }
+            (_, Some((subscribe_id, len))) if subscribe_id == id => Response::Subscribe(len),
+            _ => Response::Ignore,
+        }
+    }
+
+    fn on_response(&mut self, id: u8, data: &[u8]) {
+        self.received = Some((id, data.to_vec()));
+    }
+}
+
+#[test]
+fn protected_ids_and_checksums() {
+    let pid = |id| protected_id(id).unwrap();
+    assert_eq!(pid(0x00), 0x80);
+    assert_eq!(pid(0x01), 0xC1);
+    assert_eq!(pid(0x3C), 0x3C);
+    assert_eq!(pid(0x3D), 0x7D);
+    assert_eq!(protected_id(0x40), None);
+
+    let data = [0x4A, 0x55, 0x93, 0xE5];
+    assert_eq!(checksum(Checksum::Classic, 0xC1, &data), 0xE6);
+    assert_eq!(checksum(Checksum::Enhanced, 0xC1, &data), 0x25);
+    // diagnostic frames always use the classic checksum
+    assert_eq!(checksum(Checksum::Enhanced, 0x3C, &data), 0xE6);
+}
+
+#[test]
+fn master_publishes_to_slave() {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let mut master = Master::new(oversampled_serial(line.tx(), Wire::new(true).pin(), &clock));
+    master
+        .write_frame(0x10, &[1, 2, 3], Checksum::Enhanced)
+        .unwrap();
+
+    clock.set(0);
+    let mut slave = Slave::new(serial_receiver(&clock, &line));
+    let mut handler = TestHandler {
+        subscribe: Some((0x10, 3)),
+        ..TestHandler::default()
+    };
+    block!(slave.poll(&mut handler)).unwrap();
+    assert_eq!(handler.received, Some((0x10, vec![1, 2, 3])));
+}
+
+#[test]
+fn master_reads_slave_response() {
+    let clock = Clock::default();
+    let header = SerialLine::new(&clock);
+    let response = SerialLine::new(&clock);
+    let mut master = Master::new(oversampled_serial(
+        header.tx(),
+        Wire::new(true).pin(),
+        &clock,
+    ));
+    master.send_header(0x11).unwrap();
+
+    clock.set(0);
+    let mut slave = Slave::new(oversampled_serial(response.tx(), header.rx(), &clock));
+    let mut handler = TestHandler {
+        publish: Some((0x11, vec![0xAA, 0x55, 0x01, 0x02])),
+        ..TestHandler::default()
+    };
+    block!(slave.poll(&mut handler)).unwrap();
+
+    // same header again, with the slave response on the master RX
+    clock.set(0);
+    let mut master = Master::new(oversampled_serial(
+        SerialLine::new(&clock).tx(),
+        response.rx(),
+        &clock,
+    ));
+    let mut data = [0; 4];
+    master
+        .read_frame(0x11, &mut data, Checksum::Enhanced)
+        .unwrap();
+    assert_eq!(data, [0xAA, 0x55, 0x01, 0x02]);
+}
+
+#[test]
+fn master_reports_missing_response() {
+    let clock = Clock::default();
+    let mut master: Master<LineTx, SimPin, SimTimer> = Master::new(oversampled_serial(
+        SerialLine::new(&clock).tx(),
+        Wire::new(true).pin(),
+        &clock,
+    ));
+
+    let mut data = [0; 2];
+    let result = master.read_frame(0x20, &mut data, Checksum::Classic);
+    assert!(matches!(result, Err(Error::NoResponse)));
+}
+
+#[test]
+fn master_rejects_responses_over_max_data() {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let mut master = Master::new(oversampled_serial(line.tx(), Wire::new(true).pin(), &clock));
+
+    let mut data = [0; MAX_DATA + 1];
+    let result = master.write_frame(0x10, &data, Checksum::Enhanced);
+    assert!(matches!(result, Err(Error::DataLength)));
+    let result = master.read_frame(0x11, &mut data, Checksum::Enhanced);
+    assert!(matches!(result, Err(Error::DataLength)));
+    assert_eq!(line.first_change(), None);
+}
@@ -0,0 +1,147 @@
+mod common;
+
+use bitbang_hal::midi::{Message, Midi, Parser};
+use common::{serial_receiver, serial_sender, Clock, LineRx, SerialLine, SimPin, SimTimer};
+use nb::block;
+
+/// Messages completed by feeding `bytes` to a parser
+fn parse(bytes: &[u8]) -> Vec<String> {
+    let mut parser = Parser::<8>::new();
+    bytes
+        .iter()
+        .filter_map(|&byte| parser.feed(byte).map(|message| format!("{:?}", message)))
+        .collect()
+}
+
+fn debug(messages: &[Message]) -> Vec<String> {
+    messages
+        .iter()
+        .map(|message| format!("{:?}", message))
+        .collect()
+}
+
+#[test]
+fn running_status_repeats_last_status() {
+    let messages = parse(&[0x91, 60, 100, 64, 90, 0xC2, 5, 6, 0xE0, 0x00, 0x40]);
+    assert_eq!(
+        messages,
+        debug(&[
+            Message::NoteOn {
+                channel: 1,
+                note: 60,
+                velocity: 100
+            },
+            Message::NoteOn {
+                channel: 1,
+                note: 64,
+                velocity: 90
+            },
+            Message::ProgramChange {
+                channel: 2,
+                program: 5
+            },
+            Message::ProgramChange {
+                channel: 2,
+                program: 6
+            },
+            Message::PitchBend {
+                channel: 0,
+                value: 8192
+            },
+        ])
+    );
+}
+
+#[test]
+fn realtime_messages_interleave_other_messages() {
+    let messages = parse(&[0xB3, 0xF8, 7, 0xFA, 127, 0xF0, 1, 0xF8, 2, 0xF7, 0xFE]);
+    assert_eq!(
+        messages,
+        debug(&[
+            Message::Clock,
+            Message::Start,
+            Message::ControlChange {
+                channel: 3,
+                controller: 7,
+                value: 127
+            },
+            Message::Clock,
+            Message::SysEx(&[1, 2]),
+            Message::ActiveSensing,
+        ])
+    );
+}
+
+#[test]
+fn system_messages_cancel_running_status() {
+    let messages = parse(&[
+        0x90, 60, 100, 0xF0, 0x7E, 0x7F, 0xF7, 61, 0x80, 60, 0, 0xF2, 0x10, 0x02, 61, 0xF6,
+    ]);
+    assert_eq!(
+        messages,
+        debug(&[
+            Message::NoteOn {
+                channel: 0,
+                note: 60,
+                velocity: 100
+            },
+            Message::SysEx(&[0x7E, 0x7F]),
+            Message::NoteOff {
+                channel: 0,
+                note: 60,
+                velocity: 0
+            },
+            Message::SongPosition(0x110),
+            Message::TuneRequest,
+        ])
+    );
+}
+
+#[test]
+fn long_sysex_is_truncated() {
+    let mut bytes = vec![0xF0];
+    bytes.extend(0..20);
+    bytes.push(0xF7);
+    assert_eq!(
+        parse(&bytes),
+        debug(&[Message::SysEx(&[0, 1, 2, 3, 4, 5, 6, 7])])
+    );
+}
+
+#[test]
+fn messages_round_trip() {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let mut sender: Midi<_, _, _, 0> = Midi::new(serial_sender(&clock, &line));
+    let sent = [
+        Message::NoteOn {
+            channel: 9,
+            note: 36,
+            velocity: 127,
+        },
+        Message::ControlChange {
+            channel: 15,
+            controller: 64,
+            value: 0,
+        },
+        Message::ProgramChange {
+            channel: 0,
+            program: 42,
+        },
+        Message::PitchBend {
+            channel: 4,
+            value: 0x3FFF,
+        },
+        Message::SysEx(&[0x43, 0x10, 0x4C]),
+        Message::Clock,
+    ];
+    for message in &sent {
+        sender.send(message).unwrap();
+    }
+
+    clock.set(0);
+    let mut midi: Midi<SimPin, LineRx, SimTimer, 16> = Midi::new(serial_receiver(&clock, &line));
+    for message in &sent {
+        assert_eq!(block!(midi.receive()).unwrap(), *message);
+    }
+}
@@ -0,0 +1,202 @@
+mod common;
+
+use bitbang_hal::modbus::{crc16, Error, Exception, Master};
+use bitbang_hal::serial::{DataBits, Parity, SerialConfig, StopBits};
+use common::{
+    oversampled_serial, serial_receiver, serial_sender, Clock, LineRx, LineTx, SerialLine,
+    SimTimer, BIT,
+};
+use core::convert::Infallible;
+use embedded_hal::serial::Write;
+use nb::block;
+
+const CONFIG_8E1: SerialConfig = SerialConfig {
+    data_bits: DataBits::Eight,
+    parity: Parity::Even,
+    stop_bits: StopBits::One,
+};
+
+/// `bytes` followed by their CRC
+fn frame(bytes: &[u8]) -> Vec<u8> {
+    let mut frame = bytes.to_vec();
+    frame.extend_from_slice(&crc16(bytes).to_le_bytes());
+    frame
+}
+
+/// Run `request` on a master whose slave sends `response` at bit 200,
+/// returning its result and the bytes sent by the master
+fn transaction<T>(
+    response: &[u8],
+    request: impl FnOnce(&mut Master<LineTx, LineRx, SimTimer>) -> Result<T, Error<Infallible>>,
+) -> (Result<T, Error<Infallible>>, Vec<u8>) {
+    let clock = Clock::default();
+    let requests = SerialLine::new(&clock);
+    let responses = SerialLine::new(&clock);
+
+    clock.set(200 * BIT);
+    let mut slave = serial_sender(&clock, &responses);
+    slave.set_config(CONFIG_8E1);
+    for &byte in response {
+        block!(slave.write(byte)).unwrap();
+    }
+
+    clock.set(0);
+    let serial = oversampled_serial(requests.tx(), responses.rx(), &clock);
+    let mut master = Master::new(serial);
+    master.set_response_timeout(400);
+    let result = request(&mut master);
+
+    // decode the request
+    clock.set(0);
+    let mut serial = serial_receiver(&clock, &requests);
+    serial.set_config(CONFIG_8E1);
+    let mut sent = Vec::new();
+    while let Ok(byte) = serial.read_timeout::<u8>(100) {
+        sent.push(byte);
+    }
+    (result, sent)
+}
+
+#[test]
+fn crc_of_known_frame() {
+    assert_eq!(crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01]), 0x0A84);
+    assert_eq!(
+        frame(&[0x11, 0x05, 0x00, 0xAC, 0xFF, 0x00]),
+        [0x11, 0x05, 0x00, 0xAC, 0xFF, 0x00, 0x4E, 0x8B]
+    );
+}
+
+#[test]
+fn reads_holding_registers() {
+    let response = frame(&[0x11, 0x03, 0x06, 0xAE, 0x41, 0x56, 0x52, 0x43, 0x40]);
+    let mut registers = [0; 3];
+    let (result, sent) = transaction(&response, |master| {
+        master.read_holding_registers(0x11, 0x006B, &mut registers)
+    });
+    result.unwrap();
+    assert_eq!(sent, frame(&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03]));
+    assert_eq!(registers, [0xAE41, 0x5652, 0x4340]);
+}
+
+#[test]
+fn reads_coils() {
+    let response = frame(&[0x04, 0x01, 0x02, 0b1100_1101, 0b01]);
+    let mut coils = [false; 10];
+    let (result, sent) = transaction(&response, |master| {
+        master.read_coils(0x04, 0x0013, &mut coils)
+    });
+    result.unwrap();
+    assert_eq!(sent, frame(&[0x04, 0x01, 0x00, 0x13, 0x00, 0x0A]));
+    assert_eq!(
+        coils,
+        [true, false, true, true, false, false, true, true, true, false]
+    );
+}
+
+#[test]
+fn writes_multiple_registers_and_coils() {
+    let response = frame(&[0x11, 0x10, 0x00, 0x01, 0x00, 0x02]);
+    let (result, sent) = transaction(&response, |master| {
+        master.write_multiple_registers(0x11, 0x0001, &[0x000A, 0x0102])
+    });
+    result.unwrap();
+    assert_eq!(
+        sent,
+        frame(&[0x11, 0x10, 0x00, 0x01, 0x00, 0x02, 0x04, 0x00, 0x0A, 0x01, 0x02])
+    );
+
+    let response = frame(&[0x11, 0x0F, 0x00, 0x13, 0x00, 0x0A]);
+    let coils = [
+        true, false, true, true, false, false, true, true, true, false,
+    ];
+    let (result, sent) = transaction(&response, |master| {
+        master.write_multiple_coils(0x11, 0x0013, &coils)
+    });
+    result.unwrap();
+    assert_eq!(
+        sent,
+        frame(&[0x11, 0x0F, 0x00, 0x13, 0x00, 0x0A, 0x02, 0xCD, 0x01])
+    );
+}
+
+#[test]
+fn single_write_must_be_echoed() {
+    let request = [0x11, 0x06, 0x00, 0x01, 0x00, 0x03];
+    let (result, sent) = transaction(&frame(&request), |master| {
+        master.write_single_register(0x11, 0x0001, 0x0003)
+    });
+    result.unwrap();
+    assert_eq!(sent, frame(&request));
+
+    let response = frame(&[0x11, 0x05, 0x00, 0xAC, 0x00, 0x00]);
+    let (result, _) = transaction(&response, |master| {
+        master.write_single_coil(0x11, 0x00AC, true)
+    });
+    assert!(matches!(result, Err(Error::InvalidResponse)));
+}
+
+#[test]
+fn exception_response_is_reported() {
+    let response = frame(&[0x0A, 0x84, 0x02]);
+    let mut registers = [0; 1];
+    let (result, _) = transaction(&response, |master| {
+        master.read_input_registers(0x0A, 0x1000, &mut registers)
+    });
+    assert!(matches!(
+        result,
+        Err(Error::Exception(Exception::IllegalDataAddress))
+    ));
+}
+
+#[test]
+fn corrupted_or_missing_response_is_reported() {
+    let mut response = frame(&[0x01, 0x02, 0x01, 0x01]);
+    response[3] ^= 0x10;
+    let mut inputs = [false; 1];
+    let (result, _) = transaction(&response, |master| {
+        master.read_discrete_inputs(0x01, 0x0000, &mut inputs)
+    });
+    assert!(matches!(result, Err(Error::Crc)));
+
+    let (result, _) = transaction(&[], |master| {
+        master.read_discrete_inputs(0x01, 0x0000, &mut inputs)
+    });
+    assert!(matches!(result, Err(Error::NoResponse)));
+}
+
+#[test]
+fn broadcast_does_not_wait_for_response() {
+    let (result, sent) = transaction(&[], |master| master.write_single_coil(0, 0x0001, false));
+    result.unwrap();
+    assert_eq!(sent, frame(&[0x00, 0x05, 0x00, 0x01, 0x00, 0x00]));
+
+    let mut registers = [0; 1];
+    let (result, _) = transaction(&[], |master| {
+        master.read_holding_registers(0, 0x0001, &mut registers)
+    });
+    assert!(matches!(result, Err(Error::InvalidRequest)));
+}
+
+#[test]
+fn frames_without_parity_have_two_stop_bits() {
+    let clock = Clock::default();
+    let requests = SerialLine::new(&clock);
+    let responses = SerialLine::new(&clock);
+    let serial = oversampled_serial(requests.tx(), responses.rx(), &clock);
+    let mut master = Master::new(serial);
+    master.set_parity(Parity::None);
+    master.write_single_coil(0, 0x0001, true).unwrap();
+
+    clock.set(0);
+    let mut serial = serial_receiver(&clock, &requests);
+    serial.set_config(SerialConfig {
+        data_bits: DataBits::Eight,
+        parity: Parity::None,
+        stop_bits: StopBits::Two,
+    });
+    let mut sent = Vec::new();
+    while let Ok(byte) = serial.read_timeout::<u8>(100) {
+        sent.push(byte);
+    }
+    assert_eq!(sent, frame(&[0x00, 0x05, 0x00, 0x01, 0xFF, 0x00]));
+}
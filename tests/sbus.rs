@@ -0,0 +1,136 @@
+mod common;
+
+use bitbang_hal::sbus::{Error, Frame, Sbus, CHANNELS, FRAME_LEN};
+use bitbang_hal::serial::{DataBits, NoTx, Parity, SerialConfig, StopBits};
+use common::{oversampled_serial, serial_sender, Clock, LineRx, SerialLine, SimTimer, BIT};
+use embedded_hal::digital::v2::OutputPin;
+use embedded_hal::serial::Write;
+use nb::block;
+
+/// Bit times per byte in 8E2 format
+const BYTE_BITS: u64 = 12;
+
+/// Bytes of a frame with `channels` and `flags`
+fn encode(channels: &[u16; CHANNELS], flags: u8) -> [u8; FRAME_LEN] {
+    let mut bytes = [0; FRAME_LEN];
+    bytes[0] = 0x0F;
+    for (i, &channel) in channels.iter().enumerate() {
+        for bit in 0..11 {
+            if channel & (1 << bit) != 0 {
+                let position = i * 11 + bit;
+                bytes[1 + position / 8] |= 1 << (position % 8);
+            }
+        }
+    }
+    bytes[23] = flags;
+    bytes
+}
+
+/// Send `frames` on `line` as 8E2 frames, inverted or not, every 7 ms from
+/// bit 100
+fn send(clock: &Clock, line: &SerialLine, frames: &[[u8; FRAME_LEN]], inverted: bool) {
+    // idle level
+    clock.set(0);
+    line.tx().set_state((!inverted).into()).unwrap();
+    for (i, frame) in frames.iter().enumerate() {
+        clock.set((100 + 700 * i as u64) * BIT);
+        let mut serial = serial_sender(clock, line);
+        serial.set_config(SerialConfig {
+            data_bits: DataBits::Eight,
+            parity: Parity::Even,
+            stop_bits: StopBits::Two,
+        });
+        serial.set_tx_inverted(inverted).unwrap();
+        for &byte in frame.iter() {
+            block!(serial.write(byte)).unwrap();
+        }
+    }
+}
+
+/// Receiver with 16x oversampling, reading `line` from `start`
+fn receiver(clock: &Clock, line: &SerialLine, start: u64) -> Sbus<NoTx, LineRx, SimTimer> {
+    clock.set(start);
+    Sbus::new(oversampled_serial(NoTx::new(), line.rx(), clock))
+}
+
+#[test]
+fn decodes_channels_and_flags() {
+    let mut channels = [0; CHANNELS];
+    for (i, channel) in channels.iter_mut().enumerate() {
+        *channel = 172 + 109 * i as u16;
+    }
+    channels[15] = 0x7FF;
+    let bytes = encode(&channels, 0b1101);
+    assert_eq!(
+        Frame::decode(&bytes),
+        Some(Frame {
+            channels,
+            digital: [true, false],
+            frame_lost: true,
+            failsafe: true,
+        })
+    );
+
+    let mut bytes = encode(&channels, 0);
+    bytes[24] = 0x14;
+    assert!(Frame::decode(&bytes).is_some());
+    bytes[24] = 0x01;
+    assert_eq!(Frame::decode(&bytes), None);
+}
+
+#[test]
+fn receives_inverted_frames() {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let first = [992; CHANNELS];
+    let mut second = first;
+    second[2] = 1811;
+    send(
+        &clock,
+        &line,
+        &[encode(&first, 0), encode(&second, 0b0010)],
+        true,
+    );
+
+    let mut sbus = receiver(&clock, &line, 0);
+    let frame = block!(sbus.receive()).unwrap();
+    assert_eq!(frame.channels, first);
+    assert_eq!(frame.digital, [false, false]);
+    let frame = block!(sbus.receive()).unwrap();
+    assert_eq!(frame.channels, second);
+    assert_eq!(frame.digital, [false, true]);
+}
+
+#[test]
+fn receives_frames_behind_an_inverter() {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let mut channels = [992; CHANNELS];
+    channels[0] = 172;
+    send(&clock, &line, &[encode(&channels, 0b0001)], false);
+
+    let mut sbus = receiver(&clock, &line, 0);
+    sbus.set_inverted(false);
+    let frame = block!(sbus.receive()).unwrap();
+    assert_eq!(frame.channels, channels);
+    assert_eq!(frame.digital, [true, false]);
+}
+
+#[test]
+fn frame_boundary_is_found_from_pause() {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    // header values within the channel data
+    let channels = [0x0F0F & 0x7FF; CHANNELS];
+    send(
+        &clock,
+        &line,
+        &[encode(&channels, 0), encode(&channels, 0)],
+        true,
+    );
+
+    // start receiving during the stop bits of the fifth byte
+    let mut sbus = receiver(&clock, &line, (100 + 5 * BYTE_BITS - 1) * BIT);
+    assert!(matches!(block!(sbus.receive()), Err(Error::Frame)));
+    assert_eq!(block!(sbus.receive()).unwrap().channels, channels);
+}
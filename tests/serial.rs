@@ -0,0 +1,754 @@
+mod common;
+
+use core::fmt::Write as _;
+
+use bitbang_hal::serial::interrupt::{Receiver, Transmitter, Writer};
+use bitbang_hal::serial::queue::Queue;
+use bitbang_hal::serial::{
+    Autobaud, Cts, DataBits, DriverEnable, Error, HalfDuplexSerial, Oversampling, Parity, Rts,
+    Serial, SerialConfig, StopBits,
+};
+use common::{
+    serial_receiver, serial_sender, Clock, LineIo, LineRx, LineTx, SerialLine, SimPin, SimTimer,
+    Wire, BIT,
+};
+use embedded_hal::blocking::serial::Write as _;
+use embedded_hal::digital::v2::{OutputPin, PinState};
+use embedded_hal::serial::{Read, Write};
+use embedded_hal::timer::CountDown;
+use nb::block;
+
+/// Send every word value at the nominal rate, then receive them with a
+/// receiver running at `rate_percent` of it, whose first tick comes `phase`
+/// eighths of a timer period after the sender's first start bit
+fn round_trip(config: SerialConfig, oversampling: Oversampling, rate_percent: u64, phase: u64) {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let words = 0..1 << config.data_bits as u16;
+
+    let mut sender = serial_sender(&clock, &line);
+    sender.set_config(config);
+    for word in words.clone() {
+        block!(sender.write(word)).unwrap();
+    }
+
+    clock.set(0);
+    let ticks = u64::from(oversampling.ticks_per_bit());
+    let period = BIT * 100 / rate_percent / ticks;
+    let mut receiver = Serial::new(
+        Wire::new(true).pin(),
+        line.rx(),
+        clock.timer(period, period * phase / 8),
+    );
+    receiver.set_config(config);
+    receiver.set_oversampling(oversampling);
+    for word in words {
+        let received: u16 = block!(receiver.read()).unwrap();
+        assert_eq!(
+            received, word,
+            "{:?} {:?} {}% phase {}/8",
+            config, oversampling, rate_percent, phase
+        );
+    }
+}
+
+#[test]
+fn round_trip_every_byte_value_at_any_timer_phase() {
+    for phase in 0..8 {
+        round_trip(SerialConfig::default(), Oversampling::X2, 100, phase);
+    }
+}
+
+#[test]
+fn oversampling_tolerates_baud_rate_mismatch() {
+    for oversampling in [Oversampling::X3, Oversampling::X16] {
+        for rate_percent in [97, 100, 103] {
+            for phase in [0, 3, 6] {
+                round_trip(SerialConfig::default(), oversampling, rate_percent, phase);
+            }
+        }
+    }
+}
+
+#[test]
+fn round_trip_frame_formats() {
+    let formats = [
+        (DataBits::Five, Parity::Odd, StopBits::OneAndHalf),
+        (DataBits::Six, Parity::Space, StopBits::Two),
+        (DataBits::Seven, Parity::Even, StopBits::One),
+        (DataBits::Eight, Parity::Even, StopBits::Two),
+        (DataBits::Eight, Parity::Mark, StopBits::One),
+        (DataBits::Nine, Parity::None, StopBits::One),
+    ];
+    for (data_bits, parity, stop_bits) in formats {
+        let config = SerialConfig {
+            data_bits,
+            parity,
+            stop_bits,
+        };
+        round_trip(config, Oversampling::X16, 100, 4);
+    }
+}
+
+/// Line driven with `bits`, one bit long each and starting at time 0,
+/// followed by an idle line
+fn waveform(bits: &[bool]) -> (Clock, SerialLine) {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let mut tx = line.tx();
+    for (i, bit) in bits.iter().chain(&[true]).enumerate() {
+        clock.set(i as u64 * BIT);
+        if *bit {
+            tx.set_high().unwrap();
+        } else {
+            tx.set_low().unwrap();
+        }
+    }
+    clock.set(0);
+    (clock, line)
+}
+
+/// Receiver with 16x oversampling reading back `bits`, one bit long each and
+/// starting at time 0, followed by an idle line
+fn receiver(config: SerialConfig, bits: &[bool]) -> Serial<SimPin, LineRx, SimTimer> {
+    let (clock, line) = waveform(bits);
+    let mut receiver = serial_receiver(&clock, &line);
+    receiver.set_config(config);
+    receiver
+}
+
+/// Start bit, 8 data bits of `byte` LSB first, and parity and stop bits
+fn frame(byte: u8, parity: Option<bool>, stop: bool) -> Vec<bool> {
+    let mut bits = vec![false];
+    bits.extend((0..8).map(|bit| (byte >> bit) & 1 == 1));
+    bits.extend(parity);
+    bits.push(stop);
+    bits
+}
+
+#[test]
+fn low_stop_bit_is_framing_error() {
+    let mut serial = receiver(SerialConfig::default(), &frame(0x55, None, false));
+
+    let result: nb::Result<u8, _> = serial.read();
+    assert!(matches!(result, Err(nb::Error::Other(Error::Framing))));
+}
+
+#[test]
+fn wrong_parity_bit_is_parity_error() {
+    let config = SerialConfig {
+        parity: Parity::Even,
+        ..SerialConfig::default()
+    };
+    let mut bits = frame(0x01, Some(false), true);
+    bits.extend(frame(0x01, Some(true), true));
+    let mut serial = receiver(config, &bits);
+
+    let result: nb::Result<u8, _> = serial.read();
+    assert!(matches!(result, Err(nb::Error::Other(Error::Parity))));
+    let byte: u8 = block!(serial.read()).unwrap();
+    assert_eq!(byte, 0x01);
+}
+
+#[test]
+fn disagreeing_samples_are_noise_error() {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let mut tx = line.tx();
+    tx.set_low().unwrap();
+    // flip only the middle sample of the 4th data bit
+    clock.set(4 * BIT + BIT / 2 - BIT / 32);
+    tx.set_high().unwrap();
+    clock.set(4 * BIT + BIT / 2 + BIT / 32);
+    tx.set_low().unwrap();
+    clock.set(9 * BIT);
+    tx.set_high().unwrap();
+
+    clock.set(0);
+    let period = BIT / 16;
+    let mut serial = Serial::new(
+        Wire::new(true).pin(),
+        line.rx(),
+        clock.timer(period, period / 2),
+    );
+    serial.set_oversampling(Oversampling::X16);
+
+    let result: nb::Result<u8, _> = serial.read();
+    assert!(matches!(result, Err(nb::Error::Other(Error::Noise))));
+}
+
+#[test]
+fn low_line_for_whole_frame_is_break() {
+    let mut serial = receiver(SerialConfig::default(), &[false; 12]);
+
+    let result: nb::Result<u8, _> = serial.read();
+    assert!(matches!(result, Err(nb::Error::Other(Error::Break))));
+}
+
+#[test]
+fn read_on_idle_line_would_block() {
+    let mut serial = receiver(SerialConfig::default(), &[]);
+
+    let result: nb::Result<u8, _> = serial.read();
+    assert!(matches!(result, Err(nb::Error::WouldBlock)));
+}
+
+#[test]
+fn read_timeout_gives_up_on_idle_line() {
+    let mut serial = receiver(SerialConfig::default(), &[]);
+
+    let result: Result<u8, _> = serial.read_timeout(20);
+    assert!(matches!(result, Err(Error::Timeout)));
+}
+
+#[test]
+fn read_timeout_returns_word_received_in_time() {
+    let mut bits = vec![true; 10];
+    bits.extend(frame(0xA5, None, true));
+    let mut serial = receiver(SerialConfig::default(), &bits);
+
+    let byte: u8 = serial.read_timeout(20).unwrap();
+    assert_eq!(byte, 0xA5);
+}
+
+#[test]
+fn read_timeout_saturates_longest_timeout() {
+    let mut bits = vec![true; 10];
+    bits.extend(frame(0xA5, None, true));
+    let mut serial = receiver(SerialConfig::default(), &bits);
+
+    let byte: u8 = serial.read_timeout(u32::MAX).unwrap();
+    assert_eq!(byte, 0xA5);
+}
+
+#[test]
+fn interrupt_receiver_queues_words_and_reports_overrun() {
+    let mut bits = frame(0x12, None, true);
+    bits.extend(frame(0x34, None, true));
+    bits.extend(frame(0x56, None, true));
+    let (clock, line) = waveform(&bits);
+    let mut timer = clock.timer(BIT / 16, BIT / 32);
+    let mut queue: Queue<u8, 2> = Queue::new();
+    let (producer, mut consumer) = queue.split();
+    let mut receiver = Receiver::new(line.rx(), producer);
+    receiver.set_oversampling(Oversampling::X16);
+
+    let mut overruns = 0;
+    while clock.now() < 32 * BIT {
+        block!(timer.wait()).unwrap();
+        if let Err(Error::Overrun) = receiver.on_tick() {
+            overruns += 1;
+        }
+    }
+
+    assert_eq!(overruns, 1);
+    assert_eq!(consumer.dequeue(), Some(0x12));
+    assert_eq!(consumer.dequeue(), Some(0x34));
+    assert_eq!(consumer.dequeue(), None);
+}
+
+#[test]
+fn queue_keeps_order_and_capacity_as_counters_wrap() {
+    let mut queue: Queue<u8, 3> = Queue::new();
+    let (mut producer, mut consumer) = queue.split();
+
+    let mut next = 0;
+    for round in 0..10u8 {
+        while producer.enqueue(next).is_ok() {
+            next += 1;
+        }
+        assert!(producer.is_full());
+        assert_eq!(consumer.len(), 3);
+        for _ in 0..1 + round % 3 {
+            let front = next - consumer.len() as u8;
+            assert_eq!(consumer.dequeue(), Some(front));
+        }
+    }
+    while consumer.dequeue().is_some() {}
+    assert!(producer.is_empty());
+}
+
+/// Receive bytes sent at the nominal rate with an interrupt receiver running
+/// at `rate_percent` of it, driven by `on_tick` alone, whose first tick comes
+/// `phase` eighths of a timer period after the first start bit
+fn interrupt_round_trip(oversampling: Oversampling, rate_percent: u64, phase: u64) {
+    let bytes = [0x12, 0x34, 0xA5, 0x00, 0xFF, 0x55, 0xAA, 0x01, 0x80];
+    let bits: Vec<bool> = bytes
+        .iter()
+        .flat_map(|&byte| frame(byte, None, true))
+        .collect();
+    let (clock, line) = waveform(&bits);
+    let ticks = u64::from(oversampling.ticks_per_bit());
+    let period = BIT * 100 / rate_percent / ticks;
+    let mut timer = clock.timer(period, period * phase / 8);
+    let mut queue: Queue<u8, 16> = Queue::new();
+    let (producer, mut consumer) = queue.split();
+    let mut receiver = Receiver::new(line.rx(), producer);
+    receiver.set_oversampling(oversampling);
+
+    while clock.now() < (bits.len() as u64 + 1) * BIT {
+        block!(timer.wait()).unwrap();
+        receiver.on_tick().unwrap();
+    }
+
+    let received: Vec<u8> = core::iter::from_fn(|| consumer.dequeue()).collect();
+    assert_eq!(
+        received, bytes,
+        "{:?} {}% phase {}/8",
+        oversampling, rate_percent, phase
+    );
+}
+
+#[test]
+fn interrupt_receiver_finds_start_bit_on_tick() {
+    for phase in 0..8 {
+        interrupt_round_trip(Oversampling::X2, 100, phase);
+    }
+    for oversampling in [Oversampling::X3, Oversampling::X16] {
+        for rate_percent in [97, 100, 103] {
+            for phase in [0, 3, 6] {
+                interrupt_round_trip(oversampling, rate_percent, phase);
+            }
+        }
+    }
+}
+
+#[test]
+fn interrupt_transmitter_sends_queued_words_before_flush_completes() {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let mut timer = clock.timer(BIT / 3, BIT / 3);
+    let mut queue: Queue<u8, 4> = Queue::new();
+    let (producer, consumer) = queue.split();
+    let mut writer = Writer::new(producer);
+    let mut transmitter = Transmitter::new(line.tx(), consumer);
+
+    writer.bwrite_all(b"Hi!").unwrap();
+    let mut ticks = 0;
+    while writer.flush().is_err() {
+        block!(timer.wait()).unwrap();
+        transmitter.on_tick().unwrap();
+        ticks += 1;
+    }
+    // one tick to start, then three 10-bit frames of three ticks per bit
+    assert_eq!(ticks, 91);
+    assert!(!transmitter.is_busy());
+
+    clock.set(0);
+    let mut receiver = serial_receiver(&clock, &line);
+    for expected in b"Hi!" {
+        let byte: u8 = block!(receiver.read()).unwrap();
+        assert_eq!(byte, *expected);
+    }
+}
+
+/// Half-duplex device with 16x oversampling on a line driven with `bits` by
+/// another device, as `waveform` does
+///
+/// Also returns the levels driven by the device.
+fn half_duplex(bits: &[bool]) -> (HalfDuplexSerial<LineIo, SimTimer>, SerialLine) {
+    let (clock, line) = waveform(bits);
+    let pin = LineIo::new(&line);
+    let driven = pin.driven.clone();
+    let mut serial = HalfDuplexSerial::new(pin, clock.timer(BIT / 16, BIT / 32));
+    serial.set_oversampling(Oversampling::X16);
+    (serial, driven)
+}
+
+#[test]
+fn half_duplex_reads_echo_then_reply_after_turnaround() {
+    let mut bits = vec![true; 15];
+    bits.extend(frame(0x99, None, true));
+    let (mut serial, driven) = half_duplex(&bits);
+    serial.set_turnaround(2);
+
+    block!(serial.write(0x42u8)).unwrap();
+    let echo: u8 = block!(serial.read()).unwrap();
+    assert_eq!(echo, 0x42);
+    let reply: u8 = block!(serial.read()).unwrap();
+    assert_eq!(reply, 0x99);
+
+    // the turnaround is counted in timer ticks, the first one being partial
+    let start = driven.first_change().unwrap();
+    assert!(start >= 2 * BIT - BIT / 16, "start bit at {}", start);
+}
+
+#[test]
+fn half_duplex_suppresses_echo() {
+    let mut bits = vec![true; 15];
+    bits.extend(frame(0x99, None, true));
+    let (mut serial, _) = half_duplex(&bits);
+    serial.set_echo_suppression(true);
+
+    block!(serial.write(0x42u8)).unwrap();
+    let reply: u8 = block!(serial.read()).unwrap();
+    assert_eq!(reply, 0x99);
+}
+
+#[test]
+fn half_duplex_detects_collision() {
+    // another device pulls the line low during our 3rd data bit
+    let mut bits = vec![true; 3];
+    bits.push(false);
+    let (mut serial, _) = half_duplex(&bits);
+
+    let result = block!(serial.write(0xFFu8));
+    assert!(matches!(result, Err(Error::Collision)));
+}
+
+#[test]
+fn half_duplex_reads_bits_back_after_slow_rising_edges() {
+    for oversampling in [Oversampling::X2, Oversampling::X3, Oversampling::X16] {
+        let (clock, line) = waveform(&[]);
+        let mut pin = LineIo::new(&line);
+        pin.rise_time = BIT / 4;
+        let period = BIT / u64::from(oversampling.ticks_per_bit());
+        let mut serial = HalfDuplexSerial::new(pin, clock.timer(period, period));
+        serial.set_oversampling(oversampling);
+
+        block!(serial.write(0x55u8)).unwrap();
+    }
+}
+
+#[test]
+fn driver_enable_stays_asserted_until_flush_or_read() {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let de_line = SerialLine::new(&clock);
+    let mut de = DriverEnable::new(de_line.tx(), PinState::High);
+    de.set_delays(1, 2);
+    let mut serial = Serial::with_driver_enable(
+        line.tx(),
+        Wire::new(true).pin(),
+        clock.timer(BIT / 3, BIT / 3),
+        de,
+    )
+    .unwrap();
+
+    block!(serial.write(0x55u8)).unwrap();
+    block!(serial.write(0xAAu8)).unwrap();
+    assert_eq!(de_line.changes(), [(0, false), (0, true)]);
+    assert_eq!(line.first_change(), Some(BIT));
+
+    block!(Write::<u8>::flush(&mut serial)).unwrap();
+    assert_eq!(
+        de_line.changes(),
+        [(0, false), (0, true), (23 * BIT, false)]
+    );
+
+    block!(serial.write(0x55u8)).unwrap();
+    let result: nb::Result<u8, _> = serial.read();
+    assert!(matches!(result, Err(nb::Error::WouldBlock)));
+    assert_eq!(
+        de_line.changes()[3..],
+        [(23 * BIT, true), (36 * BIT, false)]
+    );
+}
+
+#[test]
+fn bflush_and_formatted_writes_release_driver_enable() {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let de_line = SerialLine::new(&clock);
+    let mut de = DriverEnable::new(de_line.tx(), PinState::High);
+    de.set_delays(1, 2);
+    let mut serial = Serial::with_driver_enable(
+        line.tx(),
+        Wire::new(true).pin(),
+        clock.timer(BIT / 3, BIT / 3),
+        de,
+    )
+    .unwrap();
+
+    serial.bwrite_all(&[0x55u8, 0xAA]).unwrap();
+    assert_eq!(de_line.changes(), [(0, false), (0, true)]);
+    embedded_hal::blocking::serial::Write::<u8>::bflush(&mut serial).unwrap();
+    assert_eq!(
+        de_line.changes(),
+        [(0, false), (0, true), (23 * BIT, false)]
+    );
+
+    write!(serial, "U").unwrap();
+    assert_eq!(
+        de_line.changes()[3..],
+        [(23 * BIT, true), (36 * BIT, false)]
+    );
+}
+
+#[test]
+fn inverted_round_trip_in_sbus_format() {
+    let config = SerialConfig {
+        data_bits: DataBits::Eight,
+        parity: Parity::Even,
+        stop_bits: StopBits::Two,
+    };
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let mut sender = serial_sender(&clock, &line);
+    sender.set_config(config);
+    sender.set_tx_inverted(true).unwrap();
+    for byte in [0x0Fu8, 0x00, 0xFF, 0xA5] {
+        block!(sender.write(byte)).unwrap();
+    }
+    // idle low, high start bit, then low for the first data bit
+    assert_eq!(line.changes()[..3], [(0, false), (0, true), (BIT, false)]);
+
+    clock.set(0);
+    let mut receiver = Serial::new(
+        Wire::new(true).pin(),
+        line.rx(),
+        clock.timer(BIT / 16, BIT / 32),
+    );
+    receiver.set_config(config);
+    receiver.set_oversampling(Oversampling::X16);
+    receiver.set_rx_inverted(true);
+    for expected in [0x0F, 0x00, 0xFF, 0xA5] {
+        let byte: u8 = block!(receiver.read()).unwrap();
+        assert_eq!(byte, expected);
+    }
+}
+
+#[test]
+fn break_is_reported_once_then_reading_resumes() {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let mut sender = serial_sender(&clock, &line);
+    sender.send_break(13).unwrap();
+    block!(sender.write(0x55u8)).unwrap();
+
+    clock.set(0);
+    let mut receiver = serial_receiver(&clock, &line);
+
+    let result: Result<u8, _> = block!(receiver.read());
+    assert!(matches!(result, Err(Error::Break)));
+    let byte: u8 = block!(receiver.read()).unwrap();
+    assert_eq!(byte, 0x55);
+}
+
+/// Line carrying `bytes` sent at the nominal rate after one idle bit, and a
+/// receiver on it whose timer runs 64 times faster
+fn autobaud_receiver(bytes: &[u8]) -> Serial<SimPin, LineRx, SimTimer> {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    clock.set(BIT);
+    let mut sender = serial_sender(&clock, &line);
+    for byte in bytes {
+        block!(sender.write(*byte)).unwrap();
+    }
+
+    clock.set(0);
+    Serial::new(
+        Wire::new(true).pin(),
+        line.rx(),
+        clock.timer(BIT / 64, BIT / 64),
+    )
+}
+
+#[test]
+fn autobaud_measures_shortest_low_pulse() {
+    // low pulses of 5, 2, 1 and 1 bits
+    let mut serial = autobaud_receiver(&[0xF0, 0xFE, 0xEF]);
+
+    let bit_period = serial
+        .detect_bit_period(Autobaud::ShortestLowPulse(4), 64 * 40)
+        .unwrap();
+    assert!((63..=65).contains(&bit_period), "{}", bit_period);
+}
+
+#[test]
+fn autobaud_applies_bit_period_of_sync_character() {
+    for (sync, method) in [(0x55, Autobaud::Sync55), (0x7F, Autobaud::Sync7F)] {
+        let mut serial = autobaud_receiver(&[sync, 0xA5]);
+
+        let bit_period = serial
+            .autobaud(method, 64 * 20, |ticks| u64::from(ticks) * BIT / 64 / 16)
+            .unwrap();
+        assert_eq!(bit_period, 64);
+
+        serial.set_oversampling(Oversampling::X16);
+        let byte: u8 = block!(serial.read()).unwrap();
+        assert_eq!(byte, 0xA5);
+    }
+}
+
+#[test]
+fn autobaud_times_out_on_idle_line() {
+    let mut serial = autobaud_receiver(&[]);
+
+    let result = serial.detect_bit_period(Autobaud::Sync55, 64 * 20);
+    assert!(matches!(result, Err(Error::Timeout)));
+}
+
+#[test]
+fn cts_holds_transmission_until_timeout() {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let cts = Wire::new(true);
+    let mut serial = Serial::with_flow_control(
+        line.tx(),
+        Wire::new(true).pin(),
+        clock.timer(BIT / 3, BIT / 3),
+        Cts::new(cts.pin(), PinState::Low),
+        (),
+    )
+    .unwrap();
+    serial.set_cts_timeout(Some(5));
+
+    let result = block!(serial.write(0x55u8));
+    assert!(matches!(result, Err(Error::Timeout)));
+    assert!(clock.now() >= 5 * BIT);
+    assert_eq!(line.first_change(), None);
+
+    cts.pin().set_low().unwrap();
+    block!(serial.write(0x55u8)).unwrap();
+    assert!(line.first_change().is_some());
+}
+
+#[test]
+fn cts_timeout_saturates_longest_timeout() {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let mut serial = Serial::with_flow_control(
+        line.tx(),
+        Wire::new(true).pin(),
+        clock.timer(BIT / 3, BIT / 3),
+        Cts::new(Wire::new(false).pin(), PinState::Low),
+        (),
+    )
+    .unwrap();
+    serial.set_cts_timeout(Some(u32::MAX));
+
+    block!(serial.write(0x55u8)).unwrap();
+    assert_eq!(line.first_change(), Some(0));
+}
+
+#[test]
+fn rts_deasserted_after_reading_a_word() {
+    let (clock, line) = waveform(&frame(0x42, None, true));
+    let rts = Wire::new(false);
+    let mut serial = Serial::with_flow_control(
+        Wire::new(true).pin(),
+        line.rx(),
+        clock.timer(BIT / 16, BIT / 32),
+        (),
+        Rts::new(rts.pin(), PinState::Low),
+    )
+    .unwrap();
+    serial.set_oversampling(Oversampling::X16);
+    assert!(rts.is_high());
+
+    let byte: u8 = block!(serial.read()).unwrap();
+    assert_eq!(byte, 0x42);
+    assert!(rts.is_high());
+    let result: nb::Result<u8, _> = serial.read();
+    assert!(matches!(result, Err(nb::Error::WouldBlock)));
+    assert!(!rts.is_high());
+}
+
+#[test]
+fn interrupt_receiver_deasserts_rts_while_queue_nearly_full() {
+    let mut bits = frame(0x12, None, true);
+    bits.extend(frame(0x34, None, true));
+    bits.extend(frame(0x56, None, true));
+    let (clock, line) = waveform(&bits);
+    let mut timer = clock.timer(BIT / 16, BIT / 32);
+    let rts = Wire::new(true);
+    let mut queue: Queue<u8, 4> = Queue::new();
+    let (producer, mut consumer) = queue.split();
+    let mut receiver = Receiver::with_rts(line.rx(), producer, Rts::new(rts.pin(), PinState::Low));
+    receiver.set_oversampling(Oversampling::X16);
+
+    while clock.now() < 32 * BIT {
+        block!(timer.wait()).unwrap();
+        receiver.on_tick().unwrap();
+    }
+    // one free slot left
+    assert!(rts.is_high());
+
+    assert_eq!(consumer.dequeue(), Some(0x12));
+    block!(timer.wait()).unwrap();
+    receiver.on_tick().unwrap();
+    assert!(!rts.is_high());
+}
+
+/// Sender and 16x oversampling receiver on the same line, the receiver
+/// reading from time 0 once the sender is done
+fn loopback() -> (
+    Serial<LineTx, SimPin, SimTimer>,
+    Serial<SimPin, LineRx, SimTimer>,
+    Clock,
+) {
+    let clock = Clock::default();
+    let line = SerialLine::new(&clock);
+    let sender = serial_sender(&clock, &line);
+    let receiver = serial_receiver(&clock, &line);
+    (sender, receiver, clock)
+}
+
+#[test]
+fn formatted_write() {
+    let (mut sender, mut receiver, clock) = loopback();
+
+    write!(sender, "x={}", 42).unwrap();
+    sender.bwrite_all(b"!").unwrap();
+
+    clock.set(0);
+    for expected in b"x=42!" {
+        let byte: u8 = block!(receiver.read()).unwrap();
+        assert_eq!(byte, *expected);
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+#[test]
+fn embedded_io_reads_back_to_back_bytes() {
+    let (mut sender, mut receiver, clock) = loopback();
+
+    embedded_io::Write::write_all(&mut sender, b"abc").unwrap();
+
+    clock.set(0);
+    let mut buf = [0; 8];
+    let count = embedded_io::Read::read(&mut receiver, &mut buf).unwrap();
+    assert_eq!(&buf[..count], b"abc");
+}
+
+#[test]
+fn multiprocessor_mode_skips_words_for_other_addresses() {
+    let config = SerialConfig {
+        data_bits: DataBits::Nine,
+        ..SerialConfig::default()
+    };
+    let (mut sender, mut receiver, clock) = loopback();
+    sender.set_config(config);
+    sender.write_address(0x01).unwrap();
+    block!(sender.write(0x11u16)).unwrap();
+    sender.write_address(0x02).unwrap();
+    block!(sender.write(0x22u16)).unwrap();
+    block!(sender.write(0x33u16)).unwrap();
+    sender.write_address(0x03).unwrap();
+    block!(sender.write(0x44u16)).unwrap();
+
+    clock.set(0);
+    receiver.set_config(config);
+    receiver.set_address(Some(0x02));
+    for expected in [0x102, 0x22, 0x33] {
+        let word: u16 = block!(receiver.read()).unwrap();
+        assert_eq!(word, expected);
+    }
+    let result: Result<u16, _> = receiver.read_timeout(30);
+    assert!(matches!(result, Err(Error::Timeout)));
+}
+
+#[test]
+fn multiprocessor_mode_requires_nine_data_bits() {
+    let (mut sender, mut receiver, clock) = loopback();
+    assert!(matches!(
+        sender.write_address(0x01),
+        Err(Error::AddressMode)
+    ));
+    assert_eq!(clock.now(), 0);
+
+    receiver.set_address(Some(0x01));
+    let result: nb::Result<u8, _> = receiver.read();
+    assert!(matches!(result, Err(nb::Error::Other(Error::AddressMode))));
+}
@@ -0,0 +1,651 @@
+mod common;
+
+use bitbang_hal::spi::{
+    BitOrder, Error, HalfDuplexSPI, Lines, MultiIoSPI, SpiSlave, Transaction, MODE_0, MODE_1,
+    MODE_2, MODE_3, SPI,
+};
+use common::{DevicePin, HookTimer, IoLine, NoDelay, PinError, SimPin, Wire};
+use embedded_hal::blocking::spi::{Transfer, Write};
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal::spi::{FullDuplex, Mode};
+use nb::block;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const MODES: [Mode; 4] = [MODE_0, MODE_1, MODE_2, MODE_3];
+
+fn bit_orders() -> [BitOrder; 2] {
+    [BitOrder::MSBFirst, BitOrder::LSBFirst]
+}
+
+/// Each bit order twice, for a master and a device
+fn bit_order_pairs() -> [(BitOrder, BitOrder); 2] {
+    [
+        (BitOrder::MSBFirst, BitOrder::MSBFirst),
+        (BitOrder::LSBFirst, BitOrder::LSBFirst),
+    ]
+}
+
+/// SPI with MOSI wired back to MISO
+fn loopback(mode: Mode) -> SPI<SimPin, SimPin, SimPin, NoDelay> {
+    let data = Wire::new(false);
+    SPI::new(
+        mode,
+        data.pin(),
+        data.pin(),
+        Wire::new(false).pin(),
+        NoDelay,
+    )
+}
+
+#[test]
+fn read_without_send_has_no_data() {
+    let mut spi = loopback(MODE_0);
+
+    assert!(matches!(spi.read(), Err(nb::Error::Other(Error::NoData))));
+}
+
+#[test]
+fn received_byte_is_read_once() {
+    let mut spi = loopback(MODE_0);
+
+    block!(spi.send(0xA5)).unwrap();
+
+    assert_eq!(block!(spi.read()).unwrap(), 0xA5);
+    assert!(matches!(spi.read(), Err(nb::Error::Other(Error::NoData))));
+}
+
+#[test]
+fn received_byte_does_not_include_previous_one() {
+    let mut spi = loopback(MODE_0);
+
+    block!(spi.send(0xFF)).unwrap();
+    assert_eq!(block!(spi.read()).unwrap(), 0xFF);
+    block!(spi.send(0x00)).unwrap();
+
+    assert_eq!(block!(spi.read()).unwrap(), 0x00);
+}
+
+#[test]
+fn send_twice_without_read_overruns() {
+    let mut spi = loopback(MODE_0);
+
+    block!(spi.send(0x12)).unwrap();
+    block!(spi.send(0x34)).unwrap();
+
+    assert!(matches!(spi.read(), Err(nb::Error::Other(Error::Overrun))));
+    assert_eq!(block!(spi.read()).unwrap(), 0x34);
+    assert!(matches!(spi.read(), Err(nb::Error::Other(Error::NoData))));
+}
+
+#[test]
+fn round_trip_in_every_mode_and_bit_order() {
+    for mode in MODES {
+        for bit_order in bit_orders() {
+            let mut spi = loopback(mode);
+            spi.set_bit_order(bit_order);
+
+            for byte in [0x00, 0x01, 0x80, 0x5A, 0xFF] {
+                block!(spi.send(byte)).unwrap();
+                assert_eq!(block!(spi.read()).unwrap(), byte);
+            }
+        }
+    }
+}
+
+#[test]
+fn transfer_loops_back_in_every_mode_and_bit_order() {
+    for mode in MODES {
+        for bit_order in bit_orders() {
+            let mut spi = loopback(mode);
+            spi.set_bit_order(bit_order);
+
+            let mut words = [0x00, 0x01, 0x80, 0x5A, 0xFF];
+            assert_eq!(
+                spi.transfer(&mut words).unwrap(),
+                [0x00, 0x01, 0x80, 0x5A, 0xFF]
+            );
+            assert!(matches!(spi.read(), Err(nb::Error::Other(Error::NoData))));
+        }
+    }
+}
+
+/// Slave device shared with the timer hook of a master
+type Device<Pin, const N: usize> = Rc<RefCell<SpiSlave<Pin, Pin, Pin, Pin, N>>>;
+
+/// Timer polling `device` on every tick of a master, so that it sees every
+/// clock edge
+fn poller<Pin, E, const N: usize>(device: &Device<Pin, N>) -> HookTimer<impl FnMut()>
+where
+    Pin: InputPin<Error = E> + OutputPin<Error = E>,
+    E: core::fmt::Debug,
+{
+    let device = device.clone();
+    HookTimer(move || {
+        device.borrow_mut().poll().unwrap();
+    })
+}
+
+/// Assert CS of `device`
+fn select<Pin, E, const N: usize>(cs: &mut impl OutputPin, device: &Device<Pin, N>)
+where
+    Pin: InputPin<Error = E> + OutputPin<Error = E>,
+    E: core::fmt::Debug,
+{
+    cs.set_low().ok();
+    assert_eq!(device.borrow_mut().poll().unwrap(), None);
+}
+
+/// Release CS of `device`, returning the number of bytes it received
+fn deselect<Pin, E, const N: usize>(cs: &mut impl OutputPin, device: &Device<Pin, N>) -> usize
+where
+    Pin: InputPin<Error = E> + OutputPin<Error = E>,
+    E: core::fmt::Debug,
+{
+    cs.set_high().ok();
+    device.borrow_mut().poll().unwrap().unwrap()
+}
+
+/// 3-wire device on `sdio`, answering with `response` after a command byte
+fn three_wire_device(
+    mode: Mode,
+    sck: &IoLine,
+    cs: &IoLine,
+    sdio: &IoLine,
+    response: &[u8],
+) -> Device<DevicePin, 3> {
+    let mut device = SpiSlave::new(
+        mode,
+        sck.device(),
+        cs.device(),
+        sdio.device(),
+        sdio.device(),
+    );
+    let mut preload = vec![0xFF];
+    preload.extend_from_slice(response);
+    device.preload(&preload);
+    Rc::new(RefCell::new(device))
+}
+
+#[test]
+fn half_duplex_turns_data_line_around() {
+    for mode in MODES {
+        for (device_order, bit_order) in bit_order_pairs() {
+            let (sck, cs, sdio) = (IoLine::default(), IoLine::default(), IoLine::default());
+            let device = three_wire_device(mode, &sck, &cs, &sdio, &[0xC3, 0x5A]);
+            device.borrow_mut().set_bit_order(device_order);
+            let mut spi = HalfDuplexSPI::new(mode, sdio.master(), sck.device(), poller(&device));
+            spi.set_bit_order(bit_order);
+
+            select(&mut cs.device(), &device);
+            spi.write(&[0x8E]).unwrap();
+            assert!(sdio.is_master_driven());
+            let mut input = [0; 2];
+            spi.write_read(&[], &mut input).unwrap();
+            assert!(!sdio.is_master_driven());
+            assert_eq!(deselect(&mut cs.device(), &device), 3);
+
+            assert_eq!(device.borrow().received()[0], 0x8E);
+            assert_eq!(input, [0xC3, 0x5A]);
+        }
+    }
+}
+
+#[test]
+fn half_duplex_write_read_in_one_transaction() {
+    let (sck, cs, sdio) = (IoLine::default(), IoLine::default(), IoLine::default());
+    let device = three_wire_device(MODE_3, &sck, &cs, &sdio, &[0x01, 0x80]);
+    let mut spi = HalfDuplexSPI::new(MODE_3, sdio.master(), sck.device(), poller(&device));
+
+    select(&mut cs.device(), &device);
+    let mut input = [0; 2];
+    spi.write_read(&[0xB2], &mut input).unwrap();
+    assert_eq!(deselect(&mut cs.device(), &device), 3);
+
+    assert_eq!(device.borrow().received()[0], 0xB2);
+    assert_eq!(input, [0x01, 0x80]);
+}
+
+#[test]
+fn half_duplex_pin_is_lost_after_failed_direction_change() {
+    let sdio = IoLine::default();
+    let mut spi = HalfDuplexSPI::new(MODE_0, sdio.master(), IoLine::default().device(), NoDelay);
+    spi.write(&[0x42]).unwrap();
+
+    sdio.fail_direction_changes();
+    let mut input = [0; 1];
+    assert!(matches!(spi.read(&mut input), Err(Error::Bus(PinError))));
+    assert!(matches!(spi.read(&mut input), Err(Error::PinUnavailable)));
+    assert!(matches!(spi.write(&[0x42]), Err(Error::PinUnavailable)));
+}
+
+/// Multi-IO device in mode 0, recording the levels driven by the master at
+/// each rising SCK edge, and driving `response` from cycle `respond_from`
+struct QuadDevice {
+    sck: IoLine,
+    io: [IoLine; 4],
+    sck_high: bool,
+    /// Lines driven by the master and their levels, as bits 0 (IO0) to 3
+    cycles: Vec<(u8, u8)>,
+    respond_from: usize,
+    /// Levels of IO0 to IO3 per cycle, as bits 0 to 3
+    response: Vec<u8>,
+}
+
+impl QuadDevice {
+    fn poll(&mut self) {
+        let sck_high = self.sck.level();
+        if sck_high == self.sck_high {
+            return;
+        }
+        self.sck_high = sck_high;
+
+        if sck_high {
+            let mut driven = 0;
+            let mut levels = 0;
+            for (line, io) in self.io.iter().enumerate() {
+                if io.is_master_driven() {
+                    driven |= 1 << line;
+                    levels |= u8::from(io.level()) << line;
+                }
+            }
+            self.cycles.push((driven, levels));
+        } else if let Some(bits) = self
+            .cycles
+            .len()
+            .checked_sub(self.respond_from)
+            .and_then(|cycle| self.response.get(cycle))
+        {
+            for (line, io) in self.io.iter().enumerate() {
+                if bits & (1 << line) != 0 {
+                    io.device().set_high().unwrap();
+                } else {
+                    io.device().set_low().unwrap();
+                }
+            }
+        }
+    }
+}
+
+type Quad<F> = MultiIoSPI<common::IoInput, common::IoOutput, DevicePin, HookTimer<F>, 4>;
+
+/// Quad SPI master with a device answering with `response` from cycle
+/// `respond_from`
+fn quad(respond_from: usize, response: Vec<u8>) -> (Quad<impl FnMut()>, Rc<RefCell<QuadDevice>>) {
+    let sck = IoLine::default();
+    let io = [
+        IoLine::default(),
+        IoLine::default(),
+        IoLine::default(),
+        IoLine::default(),
+    ];
+    let pins = [
+        io[0].master(),
+        io[1].master(),
+        io[2].master(),
+        io[3].master(),
+    ];
+    let device = Rc::new(RefCell::new(QuadDevice {
+        sck: sck.clone(),
+        io,
+        sck_high: false,
+        cycles: Vec::new(),
+        respond_from,
+        response,
+    }));
+    let polled = device.clone();
+    let timer = HookTimer(move || polled.borrow_mut().poll());
+    (MultiIoSPI::new(MODE_0, pins, sck.device(), timer), device)
+}
+
+/// Cycles of `bytes` sent MSB first on IO0
+fn single(bytes: &[u8]) -> Vec<(u8, u8)> {
+    bytes
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |bit| (0b0001, (byte >> bit) & 1)))
+        .collect()
+}
+
+/// Cycles of `bytes` sent as nibbles on IO0 to IO3, IO3 carrying the MSB
+fn quad_cycles(bytes: &[u8]) -> Vec<(u8, u8)> {
+    bytes
+        .iter()
+        .flat_map(|byte| [(0b1111, byte >> 4), (0b1111, byte & 0x0F)])
+        .collect()
+}
+
+/// Cycles of `bytes` sent as bit pairs on IO0 and IO1, IO1 carrying the MSB
+fn dual_cycles(bytes: &[u8]) -> Vec<(u8, u8)> {
+    bytes
+        .iter()
+        .flat_map(|byte| {
+            (0..4)
+                .rev()
+                .map(move |pair| (0b0011, (byte >> (2 * pair)) & 0b11))
+        })
+        .collect()
+}
+
+/// Bit pairs of `bytes` to be driven on IO0 and IO1, with IO2 and IO3 high
+fn pairs(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .iter()
+        .flat_map(|byte| {
+            (0..4)
+                .rev()
+                .map(move |pair| 0b1100 | (byte >> (2 * pair)) & 0b11)
+        })
+        .collect()
+}
+
+/// Nibbles of `bytes` to be driven on IO0 to IO3
+fn nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0F])
+        .collect()
+}
+
+/// `count` cycles with the data lines released by the master
+fn released(count: usize) -> Vec<(u8, u8)> {
+    vec![(0, 0); count]
+}
+
+#[test]
+fn dual_output_read_1_1_2() {
+    let (mut spi, device) = quad(8 + 24 + 8, pairs(&[0xA5, 0x3C]));
+    let transaction = Transaction {
+        instruction: Some(0x3B),
+        address: Some(0x123456),
+        dummy_cycles: 8,
+        data_lines: Lines::Dual,
+        ..Transaction::default()
+    };
+    let mut input = [0; 2];
+    spi.read(&transaction, &mut input).unwrap();
+
+    // IO2 and IO3 are driven high by the device but not sampled
+    assert_eq!(input, [0xA5, 0x3C]);
+    let expected = [single(&[0x3B, 0x12, 0x34, 0x56]), released(8), released(8)].concat();
+    assert_eq!(device.borrow().cycles, expected);
+}
+
+#[test]
+fn dual_io_read_1_2_2() {
+    let (mut spi, device) = quad(8 + 12 + 4, pairs(&[0x0F, 0x96]));
+    let transaction = Transaction {
+        instruction: Some(0xBB),
+        address: Some(0xABCDEF),
+        address_lines: Lines::Dual,
+        dummy_cycles: 4,
+        data_lines: Lines::Dual,
+        ..Transaction::default()
+    };
+    let mut input = [0; 2];
+    spi.read(&transaction, &mut input).unwrap();
+
+    assert_eq!(input, [0x0F, 0x96]);
+    let expected = [
+        single(&[0xBB]),
+        dual_cycles(&[0xAB, 0xCD, 0xEF]),
+        released(4),
+        released(8),
+    ]
+    .concat();
+    assert_eq!(device.borrow().cycles, expected);
+}
+
+#[test]
+fn dual_write_1_1_2() {
+    let (mut spi, device) = quad(usize::MAX, Vec::new());
+    let transaction = Transaction {
+        instruction: Some(0xA2),
+        address: Some(0x000100),
+        data_lines: Lines::Dual,
+        ..Transaction::default()
+    };
+    spi.write(&transaction, &[0x5A, 0xC3]).unwrap();
+
+    // IO2 and IO3 stay released
+    let expected = [
+        single(&[0xA2, 0x00, 0x01, 0x00]),
+        dual_cycles(&[0x5A, 0xC3]),
+    ]
+    .concat();
+    assert_eq!(device.borrow().cycles, expected);
+}
+
+#[test]
+fn quad_output_read_1_1_4() {
+    let (mut spi, device) = quad(8 + 24 + 8, nibbles(&[0xA5, 0x3C]));
+    let transaction = Transaction {
+        instruction: Some(0x6B),
+        address: Some(0x123456),
+        dummy_cycles: 8,
+        data_lines: Lines::Quad,
+        ..Transaction::default()
+    };
+    let mut input = [0; 2];
+    spi.read(&transaction, &mut input).unwrap();
+
+    assert_eq!(input, [0xA5, 0x3C]);
+    let expected = [single(&[0x6B, 0x12, 0x34, 0x56]), released(8), released(4)].concat();
+    assert_eq!(device.borrow().cycles, expected);
+}
+
+#[test]
+fn quad_io_read_1_4_4() {
+    let (mut spi, device) = quad(8 + 6 + 4, nibbles(&[0x0F, 0x96]));
+    let transaction = Transaction {
+        instruction: Some(0xEB),
+        address: Some(0xABCDEF),
+        address_lines: Lines::Quad,
+        dummy_cycles: 4,
+        data_lines: Lines::Quad,
+        ..Transaction::default()
+    };
+    let mut input = [0; 2];
+    spi.read(&transaction, &mut input).unwrap();
+
+    assert_eq!(input, [0x0F, 0x96]);
+    let expected = [
+        single(&[0xEB]),
+        quad_cycles(&[0xAB, 0xCD, 0xEF]),
+        released(4),
+        released(4),
+    ]
+    .concat();
+    assert_eq!(device.borrow().cycles, expected);
+}
+
+#[test]
+fn qpi_read_and_write_4_4_4() {
+    let (mut spi, device) = quad(2 + 4 + 2, nibbles(&[0x81]));
+    let transaction = Transaction {
+        instruction: Some(0x0B),
+        instruction_lines: Lines::Quad,
+        address: Some(0x0100),
+        address_bytes: 2,
+        address_lines: Lines::Quad,
+        dummy_cycles: 2,
+        data_lines: Lines::Quad,
+    };
+    let mut input = [0; 1];
+    spi.read(&transaction, &mut input).unwrap();
+    assert_eq!(input, [0x81]);
+
+    let transaction = Transaction {
+        instruction: Some(0x02),
+        ..transaction
+    };
+    spi.write(&transaction, &[0x5A, 0xC3]).unwrap();
+
+    let expected = [
+        quad_cycles(&[0x0B, 0x01, 0x00]),
+        released(2),
+        released(2),
+        quad_cycles(&[0x02, 0x01, 0x00]),
+        released(2),
+        quad_cycles(&[0x5A, 0xC3]),
+    ]
+    .concat();
+    assert_eq!(device.borrow().cycles, expected);
+}
+
+#[test]
+fn single_data_phase_reads_io1() {
+    // IO1 carries the data, IO0 its complement
+    let response = single(&[0x6C])
+        .into_iter()
+        .map(|(_, bit)| bit << 1 | (bit ^ 1))
+        .collect();
+    let (mut spi, device) = quad(8 + 24, response);
+    let transaction = Transaction {
+        instruction: Some(0x03),
+        address: Some(0x000010),
+        ..Transaction::default()
+    };
+    let mut input = [0; 1];
+    spi.read(&transaction, &mut input).unwrap();
+
+    assert_eq!(input, [0x6C]);
+    assert_eq!(
+        device.borrow().cycles[..32],
+        single(&[0x03, 0x00, 0x00, 0x10])[..]
+    );
+}
+
+/// 4-wire bus: a master and a slave device polled on every master tick
+struct Bus<F: FnMut(), const N: usize> {
+    master: SPI<SimPin, SimPin, SimPin, HookTimer<F>>,
+    device: Device<SimPin, N>,
+    cs: Wire,
+}
+
+fn bus<const N: usize>(mode: Mode, response: &[u8]) -> Bus<impl FnMut(), N> {
+    let (sck, cs, mosi, miso) = (
+        Wire::new(false),
+        Wire::new(true),
+        Wire::new(false),
+        Wire::new(false),
+    );
+    let mut device = SpiSlave::new(mode, sck.pin(), cs.pin(), mosi.pin(), miso.pin());
+    device.preload(response);
+    let device = Rc::new(RefCell::new(device));
+    let master = SPI::new(mode, miso.pin(), mosi.pin(), sck.pin(), poller(&device));
+    Bus { master, device, cs }
+}
+
+#[test]
+fn slave_exchanges_bytes_in_every_mode() {
+    for mode in MODES {
+        for (device_order, bit_order) in bit_order_pairs() {
+            let mut bus = bus::<4>(mode, &[0x3C, 0xA5, 0x81]);
+            bus.device.borrow_mut().set_bit_order(device_order);
+            bus.master.set_bit_order(bit_order);
+
+            select(&mut bus.cs.pin(), &bus.device);
+            let mut words = [0x12, 0x34, 0x56];
+            bus.master.transfer(&mut words).unwrap();
+            assert_eq!(deselect(&mut bus.cs.pin(), &bus.device), 3);
+
+            assert_eq!(words, [0x3C, 0xA5, 0x81]);
+            assert_eq!(bus.device.borrow().received(), [0x12, 0x34, 0x56]);
+        }
+    }
+}
+
+#[test]
+fn slave_sends_ff_once_preload_is_exhausted() {
+    let mut bus = bus::<4>(MODE_1, &[0x42]);
+
+    select(&mut bus.cs.pin(), &bus.device);
+    let mut words = [0x00; 3];
+    bus.master.transfer(&mut words).unwrap();
+    deselect(&mut bus.cs.pin(), &bus.device);
+    assert_eq!(words, [0x42, 0xFF, 0xFF]);
+
+    // every transaction starts over with the preloaded data
+    select(&mut bus.cs.pin(), &bus.device);
+    let mut words = [0x00; 2];
+    bus.master.transfer(&mut words).unwrap();
+    deselect(&mut bus.cs.pin(), &bus.device);
+    assert_eq!(words, [0x42, 0xFF]);
+}
+
+#[test]
+fn slave_keeps_first_n_bytes() {
+    let mut bus = bus::<2>(MODE_2, &[0x01, 0x02, 0x03]);
+
+    select(&mut bus.cs.pin(), &bus.device);
+    let mut words = [0x10, 0x20, 0x30, 0x40];
+    bus.master.transfer(&mut words).unwrap();
+    assert_eq!(deselect(&mut bus.cs.pin(), &bus.device), 2);
+
+    assert_eq!(words, [0x01, 0x02, 0xFF, 0xFF]);
+    assert_eq!(bus.device.borrow().received(), [0x10, 0x20]);
+}
+
+#[test]
+fn slave_drops_partial_byte_when_cs_is_released() {
+    let (sck, cs, mosi, miso) = (
+        Wire::new(false),
+        Wire::new(true),
+        Wire::new(false),
+        Wire::new(false),
+    );
+    let mut device =
+        SpiSlave::<_, _, _, _, 4>::new(MODE_0, sck.pin(), cs.pin(), mosi.pin(), miso.pin());
+    device.preload(&[0xC5]);
+    let device = Rc::new(RefCell::new(device));
+
+    // clock bits by hand in mode 0, returning the bits sent by the device
+    let clock_bits = |bits: &[bool]| -> Vec<bool> {
+        let mut sent = Vec::new();
+        for &bit in bits {
+            mosi.pin().set_state(bit.into()).unwrap();
+            sck.pin().set_high().unwrap();
+            device.borrow_mut().poll().unwrap();
+            sent.push(miso.is_high());
+            sck.pin().set_low().unwrap();
+            device.borrow_mut().poll().unwrap();
+        }
+        sent
+    };
+
+    select(&mut cs.pin(), &device);
+    assert_eq!(clock_bits(&[true; 4]), [true, true, false, false]);
+    assert_eq!(deselect(&mut cs.pin(), &device), 0);
+    assert!(device.borrow().received().is_empty());
+
+    // the next transaction starts on a byte boundary
+    select(&mut cs.pin(), &device);
+    let bits = [true, false, true, false, false, true, false, true];
+    assert_eq!(
+        clock_bits(&bits),
+        [true, true, false, false, false, true, false, true]
+    );
+    assert_eq!(deselect(&mut cs.pin(), &device), 1);
+    assert_eq!(device.borrow().received(), [0xA5]);
+}
+
+#[test]
+fn write_reaches_slave_in_every_mode_and_bit_order() {
+    for mode in MODES {
+        for (device_order, bit_order) in bit_order_pairs() {
+            let mut bus = bus::<4>(mode, &[]);
+            bus.device.borrow_mut().set_bit_order(device_order);
+            bus.master.set_bit_order(bit_order);
+
+            select(&mut bus.cs.pin(), &bus.device);
+            bus.master.write(&[0x01, 0x80, 0x5A]).unwrap();
+            assert_eq!(deselect(&mut bus.cs.pin(), &bus.device), 3);
+
+            assert_eq!(bus.device.borrow().received(), [0x01, 0x80, 0x5A]);
+            assert!(matches!(
+                bus.master.read(),
+                Err(nb::Error::Other(Error::NoData))
+            ));
+        }
+    }
+}